@@ -0,0 +1,55 @@
+//Times the widget tree operations on a UI with 10k widgets.
+//Run with `cargo bench --bench widgets`.
+extern crate gliumtest;
+
+use gliumtest::gui::{self, Parent, UI, WidgetHandle};
+use std::time::{Duration, Instant};
+
+const FORMS: usize = 100;
+const WIDGETS_PER_FORM: usize = 99;
+
+fn report(name: &str, elapsed: Duration, operations: usize) {
+    let millis = elapsed.as_secs() as f64 * 1000.0 + elapsed.subsec_nanos() as f64 / 1_000_000.0;
+    println!("{:<8} {:>7} operations {:>10.3} ms {:>10.3} us/op",
+             name, operations, millis, millis * 1000.0 / operations as f64);
+}
+
+fn main() {
+    let mut ui = UI::new(800, 600);
+    let mut handles: Vec<WidgetHandle> = Vec::with_capacity(FORMS * (WIDGETS_PER_FORM + 1));
+
+    let start = Instant::now();
+    for f in 0 .. FORMS {
+        let form = ui.add_widget(Parent::Root, gui::new_form(f as i32, f as i32, 400, 300, "Form")).unwrap();
+        handles.push(form);
+
+        for i in 0 .. WIDGETS_PER_FORM {
+            let button = gui::new_button(10, 10 + i as i32 * 2, 100, 40, "Button");
+            handles.push(ui.add_widget(form, button).unwrap());
+        }
+    }
+    report("add", start.elapsed(), handles.len());
+    assert_eq!(ui.num_widgets(), FORMS * (WIDGETS_PER_FORM + 1));
+
+    let start = Instant::now();
+    for handle in &handles {
+        ui.move_widget_to_front(*handle);
+    }
+    report("raise", start.elapsed(), handles.len());
+
+    let start = Instant::now();
+    let found = handles.iter().filter(|h| ui.widget(**h).is_some()).count();
+    report("lookup", start.elapsed(), handles.len());
+    assert_eq!(found, handles.len());
+
+    let start = Instant::now();
+    let jobs = ui.render();
+    report("render", start.elapsed(), jobs.len());
+
+    let start = Instant::now();
+    for handle in handles.iter().rev() {
+        ui.remove_widget(*handle);
+    }
+    report("remove", start.elapsed(), handles.len());
+    assert_eq!(ui.num_widgets(), 0);
+}
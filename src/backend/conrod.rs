@@ -0,0 +1,173 @@
+use conrod;
+use conrod::{widget, Colorable, Positionable, Sizeable, Borderable, Labelable, Widget};
+use std::collections::HashMap;
+use gui::{UI, RenderJob, WidgetHandle, FONT_SIZE, FORM_BORDER};
+use backend::{RenderBackend, TextMeasurer, text_with_caret};
+
+/***********************************************************************************
+ *      ConrodBackend
+ *
+ *      Draws the RenderJobs of a UI with conrod widgets. Every UI widget is
+ *      drawn with its own conrod widget id. The ids of removed widgets are
+ *      handed out again to new widgets, conrod can't free them.
+ ***********************************************************************************/
+pub struct ConrodBackend {
+    ids: HashMap<WidgetHandle, widget::Id>,
+    free_ids: Vec<widget::Id>,
+}
+
+impl ConrodBackend {
+    pub fn new() -> ConrodBackend {
+        ConrodBackend {
+            ids: HashMap::new(),
+            free_ids: Vec::new(),
+        }
+    }
+
+/***********************************************************************************
+ *      ConrodBackend::free_removed_ids
+ *
+ *      Takes back the ids of widgets that have been removed from the UI.
+ ***********************************************************************************/
+    pub fn free_removed_ids(&mut self, ui: &UI) {
+        let free_ids = &mut self.free_ids;
+        self.ids.retain(|handle, id| {
+            if ui.contains(*handle) {return true;}
+            free_ids.push(*id);
+            false
+        });
+    }
+
+    fn allocate_ids(&mut self, render_jobs: &[RenderJob], ui: &mut conrod::UiCell) {
+        let mut generator = ui.widget_id_generator();
+        for handle in render_jobs.iter().filter_map(|job| job.handle()) {
+            if self.ids.contains_key(&handle) {continue;}
+
+            let id = match self.free_ids.pop() {
+                Some(id) => id,
+                None => generator.next()
+            };
+            self.ids.insert(handle, id);
+        }
+    }
+}
+
+/***********************************************************************************
+ *      ConrodBackend::draw
+ *
+ *      Sets a conrod widget for every RenderJob. The UI uses the top left
+ *      corner of the window as origin, conrod the center, the offset is
+ *      taken from the current window size.
+ ***********************************************************************************/
+impl<'a> RenderBackend<conrod::UiCell<'a>> for ConrodBackend {
+    fn draw(&mut self, render_jobs: &[RenderJob], ui: &mut conrod::UiCell<'a>) {
+        self.allocate_ids(render_jobs, ui);
+
+        let half_screen_w = ui.win_w / 2.0;
+        let half_screen_h = ui.win_h / 2.0;
+
+        for render_job in render_jobs {
+            let i = match render_job.handle().and_then(|handle| self.ids.get(&handle)) {
+                Some(id) => *id,
+                None => continue //No conrod id allocated for this widget
+            };
+
+            match *render_job {
+                RenderJob::Nul => (),
+                RenderJob::Form { hovered, keyboard_focused, disabled, x, y, w, h, ref title, ..} => {
+                    let mut framecolor =  conrod::color::grayscale(0.575);
+                    if hovered {framecolor = conrod::color::grayscale(0.650);}
+                    if disabled {framecolor = conrod::color::grayscale(0.4);}
+
+                    let mut bordercolor = conrod::color::BLACK;
+                    if keyboard_focused {bordercolor = conrod::color::grayscale(0.9);}
+
+                    widget::Toggle::new(true)
+                        .x_y(x as f64 - half_screen_w + (w as f64 / 2.0), half_screen_h - y as f64 - (h as f64/ 2.0))
+                        .w_h(w as f64, h as f64)
+                        .color(framecolor)
+                        .border(FORM_BORDER as f64)
+                        .border_color(bordercolor)
+                        .set(i, ui);
+                }
+                RenderJob::Button {pressed, hovered, keyboard_focused, disabled, x, y, w, h, ref text, ..} => {
+                    let mut label_color = conrod::color::CHARCOAL;
+                    let mut bgcolor = conrod::color::DARK_BLUE;
+                    if pressed {label_color = conrod::color::LIGHT_CHARCOAL; }
+                    else {
+                        if hovered {bgcolor = conrod::color::GRAY;}
+                        else {bgcolor = conrod::color::LIGHT_CHARCOAL;}
+                    }
+                    if disabled {
+                        label_color = conrod::color::GRAY;
+                        bgcolor = conrod::color::DARK_CHARCOAL;
+                    }
+
+                    //Focus ring for the button that has keyboard focus
+                    let mut bordercolor = conrod::color::BLACK;
+                    if keyboard_focused {bordercolor = conrod::color::YELLOW;}
+
+                    widget::Toggle::new(!pressed)
+                        .x_y(x as f64 - half_screen_w + (w as f64 / 2.0), half_screen_h - y as f64 - (h as f64/ 2.0))
+                        .w_h(w as f64, h as f64)
+                        .color(bgcolor)
+                        .border_color(bordercolor)
+                        .label(&text)
+                        .label_color(label_color)
+                        .set(i, ui);
+                },
+                RenderJob::Label {disabled, x, y, ref text, ..} => {
+                    widget::Text::new(&text)
+                        .x_y(x as f64 - half_screen_w, half_screen_h - y as f64)
+                        .w_h(0.1, 0.1)
+                        .no_line_wrap()
+                        .font_size(FONT_SIZE)
+                        .color(if disabled {conrod::color::GRAY} else {conrod::color::WHITE})
+                        .set(i, ui);
+                }
+                RenderJob::Textbox {hovered, keyboard_focused, disabled, x, y, w, h, ref text, caret, ..} => {
+                    let mut bgcolor = conrod::color::LIGHT_GRAY;
+                    if hovered || keyboard_focused {bgcolor = conrod::color::WHITE;}
+                    if disabled {bgcolor = conrod::color::GRAY;}
+
+                    let label = text_with_caret(text, caret, keyboard_focused);
+
+                    widget::Toggle::new(false)
+                        .x_y(x as f64 - half_screen_w + (w as f64 / 2.0), half_screen_h - y as f64 - (h as f64/ 2.0))
+                        .w_h(w as f64, h as f64)
+                        .color(bgcolor)
+                        .label(&label)
+                        .label_color(conrod::color::BLACK)
+                        .set(i, ui);
+                }
+            }
+        }
+    }
+}
+
+/***********************************************************************************
+ *      ConrodTextMeasurer
+ *
+ *      Measures text with the font conrod draws it with.
+ ***********************************************************************************/
+pub struct ConrodTextMeasurer {
+    font: conrod::text::Font,
+}
+
+impl ConrodTextMeasurer {
+    pub fn new(font: conrod::text::Font) -> ConrodTextMeasurer {
+        ConrodTextMeasurer {
+            font: font
+        }
+    }
+}
+
+impl TextMeasurer for ConrodTextMeasurer {
+    fn line_width(&self, text: &str, font_size: u32) -> f64 {
+        conrod::text::line::width(text, &self.font, font_size)
+    }
+
+    fn line_height(&self, font_size: u32) -> f64 {
+        conrod::text::height(1, font_size, 0.0)
+    }
+}
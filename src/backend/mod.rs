@@ -0,0 +1,41 @@
+//Renderers that turn the RenderJobs of a UI into something visible
+#[cfg(feature = "conrod")]
+pub mod conrod;
+pub mod palette;
+pub mod software;
+pub mod svg;
+pub mod text;
+
+use gui::RenderJob;
+
+/***********************************************************************************
+ *      RenderBackend
+ *      
+ *      Draws the RenderJobs created by UI::render onto a target, for example
+ *      a conrod UiCell or an image. The jobs are in drawing order, later
+ *      jobs are drawn on top of earlier ones.
+ ***********************************************************************************/
+pub trait RenderBackend<T: ?Sized> {
+    fn draw(&mut self, render_jobs: &[RenderJob], target: &mut T);
+}
+
+/***********************************************************************************
+ *      TextMeasurer
+ *      
+ *      Measures single lines of text in the font a backend draws them with,
+ *      so the UI can size labels and buttons to their text. Sizes are in
+ *      pixels.
+ ***********************************************************************************/
+pub trait TextMeasurer {
+    fn line_width(&self, text: &str, font_size: u32) -> f64;
+    fn line_height(&self, font_size: u32) -> f64;
+}
+
+//Text of a text box with the caret drawn as a bar between the characters,
+//for backends that can't draw the caret on their own
+pub fn text_with_caret(text: &str, caret: usize, keyboard_focused: bool) -> String {
+    let mut line: String = text.chars().take(caret).collect();
+    if keyboard_focused {line.push('|');}
+    line.extend(text.chars().skip(caret));
+    line
+}
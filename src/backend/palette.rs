@@ -0,0 +1,21 @@
+//Colors of the software and svg backends, so both draw the same picture
+use image::Rgba;
+
+pub const BACKGROUND: Rgba<u8> = Rgba([0, 0, 0, 255]);
+pub const BLACK: Rgba<u8> = Rgba([0, 0, 0, 255]);
+pub const WHITE: Rgba<u8> = Rgba([255, 255, 255, 255]);
+pub const FORM: Rgba<u8> = Rgba([147, 147, 147, 255]);
+pub const FORM_HOVERED: Rgba<u8> = Rgba([166, 166, 166, 255]);
+pub const FORM_DISABLED: Rgba<u8> = Rgba([102, 102, 102, 255]);
+pub const FORM_FOCUSED_BORDER: Rgba<u8> = Rgba([230, 230, 230, 255]);
+pub const BUTTON: Rgba<u8> = Rgba([85, 87, 83, 255]);
+pub const BUTTON_HOVERED: Rgba<u8> = Rgba([136, 138, 133, 255]);
+pub const BUTTON_PRESSED: Rgba<u8> = Rgba([32, 74, 135, 255]);
+pub const BUTTON_DISABLED: Rgba<u8> = Rgba([46, 52, 54, 255]);
+pub const BUTTON_TEXT: Rgba<u8> = Rgba([46, 52, 54, 255]);
+pub const BUTTON_TEXT_PRESSED: Rgba<u8> = Rgba([85, 87, 83, 255]);
+pub const FOCUS_RING: Rgba<u8> = Rgba([237, 212, 0, 255]);
+pub const TEXTBOX: Rgba<u8> = Rgba([211, 215, 207, 255]);
+pub const TEXTBOX_ACTIVE: Rgba<u8> = Rgba([255, 255, 255, 255]);
+pub const SELECTION: Rgba<u8> = Rgba([114, 159, 207, 255]);
+pub const DISABLED: Rgba<u8> = Rgba([136, 138, 133, 255]);
@@ -0,0 +1,409 @@
+use image::{self, Rgba, RgbaImage};
+use rusttype;
+use std::cmp;
+use std::env;
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+use gui::{RenderJob, FONT_SIZE, FORM_BORDER};
+use backend::{RenderBackend, TextMeasurer};
+use backend::palette::{BACKGROUND, BLACK, WHITE, FORM, FORM_HOVERED, FORM_DISABLED, FORM_FOCUSED_BORDER,
+                       BUTTON, BUTTON_HOVERED, BUTTON_PRESSED, BUTTON_DISABLED, BUTTON_TEXT, BUTTON_TEXT_PRESSED,
+                       FOCUS_RING, TEXTBOX, TEXTBOX_ACTIVE, SELECTION, DISABLED};
+
+const TEXT_PADDING: i32 = 6;
+
+//Screen rect clipped against the image or another clip rect
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Clip {
+    x0: i32,
+    y0: i32,
+    x1: i32,
+    y1: i32,
+}
+
+impl Clip {
+    fn new(x: i32, y: i32, w: i32, h: i32) -> Clip {
+        Clip {x0: x, y0: y, x1: x + w, y1: y + h}
+    }
+
+    fn intersect(&self, other: Clip) -> Clip {
+        Clip {
+            x0: cmp::max(self.x0, other.x0),
+            y0: cmp::max(self.y0, other.y0),
+            x1: cmp::min(self.x1, other.x1),
+            y1: cmp::min(self.y1, other.y1),
+        }
+    }
+
+    fn contains(&self, x: i32, y: i32) -> bool {
+        x >= self.x0 && x < self.x1 && y >= self.y0 && y < self.y1
+    }
+}
+
+/***********************************************************************************
+ *      SoftwareFont
+ *
+ *      A TrueType font the software backend draws text with. It is also a
+ *      TextMeasurer, so a UI drawn with it sizes its widgets to the text.
+ ***********************************************************************************/
+#[derive(Clone)]
+pub struct SoftwareFont {
+    font: rusttype::Font<'static>,
+}
+
+impl SoftwareFont {
+    pub fn from_bytes(data: Vec<u8>) -> Option<SoftwareFont> {
+        rusttype::Font::try_from_vec(data).map(|font| SoftwareFont {font: font})
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> io::Result<SoftwareFont> {
+        let mut data = Vec::new();
+        File::open(path)?.read_to_end(&mut data)?;
+
+        match SoftwareFont::from_bytes(data) {
+            Some(font) => Ok(font),
+            None => Err(io::Error::new(io::ErrorKind::InvalidData, "not a TrueType font"))
+        }
+    }
+
+    //The font the glium window uses, so both backends measure the same
+    pub fn dogma() -> io::Result<SoftwareFont> {
+        SoftwareFont::from_file(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/fonts/Dogma/Dogma.ttf"))
+    }
+
+    fn draw_text(&self, image: &mut RgbaImage, clip: Clip, x: i32, y: i32, text: &str, color: Rgba<u8>) {
+        let scale = rusttype::Scale::uniform(FONT_SIZE as f32);
+        let ascent = self.font.v_metrics(scale).ascent;
+
+        for glyph in self.font.layout(text, scale, rusttype::point(x as f32, y as f32 + ascent)) {
+            let bounds = match glyph.pixel_bounding_box() {
+                Some(bounds) => bounds,
+                None => continue //Whitespace
+            };
+
+            glyph.draw(|gx, gy, coverage| {
+                let px = bounds.min.x + gx as i32;
+                let py = bounds.min.y + gy as i32;
+                if clip.contains(px, py) {
+                    blend_pixel(image, px, py, color, coverage);
+                }
+            });
+        }
+    }
+}
+
+impl TextMeasurer for SoftwareFont {
+    fn line_width(&self, text: &str, font_size: u32) -> f64 {
+        let scale = rusttype::Scale::uniform(font_size as f32);
+        match self.font.layout(text, scale, rusttype::point(0.0, 0.0)).last() {
+            Some(glyph) => (glyph.position().x + glyph.unpositioned().h_metrics().advance_width) as f64,
+            None => 0.0
+        }
+    }
+
+    fn line_height(&self, font_size: u32) -> f64 {
+        let metrics = self.font.v_metrics(rusttype::Scale::uniform(font_size as f32));
+        (metrics.ascent - metrics.descent + metrics.line_gap) as f64
+    }
+}
+
+fn blend_pixel(image: &mut RgbaImage, x: i32, y: i32, color: Rgba<u8>, coverage: f32) {
+    let pixel = image.get_pixel_mut(x as u32, y as u32);
+    for c in 0 .. 3 {
+        let old = pixel.0[c] as f32;
+        pixel.0[c] = (old + (color.0[c] as f32 - old) * coverage).round() as u8;
+    }
+    pixel.0[3] = 255;
+}
+
+fn fill_rect(image: &mut RgbaImage, clip: Clip, rect: Clip, color: Rgba<u8>) {
+    let area = rect.intersect(clip);
+    for y in area.y0 .. area.y1 {
+        for x in area.x0 .. area.x1 {
+            image.put_pixel(x as u32, y as u32, color);
+        }
+    }
+}
+
+fn stroke_rect(image: &mut RgbaImage, clip: Clip, rect: Clip, width: i32, color: Rgba<u8>) {
+    fill_rect(image, clip, Clip {y1: rect.y0 + width, ..rect}, color);
+    fill_rect(image, clip, Clip {y0: rect.y1 - width, ..rect}, color);
+    fill_rect(image, clip, Clip {x1: rect.x0 + width, ..rect}, color);
+    fill_rect(image, clip, Clip {x0: rect.x1 - width, ..rect}, color);
+}
+
+/***********************************************************************************
+ *      SoftwareBackend
+ *
+ *      Rasterizes RenderJobs into an RgbaImage on the CPU. Needs no window
+ *      or GPU, so the UI can be rendered in tests and on CI machines.
+ ***********************************************************************************/
+pub struct SoftwareBackend {
+    font: SoftwareFont,
+}
+
+impl SoftwareBackend {
+    pub fn new(font: SoftwareFont) -> SoftwareBackend {
+        SoftwareBackend {
+            font: font
+        }
+    }
+
+/***********************************************************************************
+ *      SoftwareBackend::render_to_image
+ *
+ *      Draws the jobs into a new black image of the given size.
+ ***********************************************************************************/
+    pub fn render_to_image(&mut self, render_jobs: &[RenderJob], width: u32, height: u32) -> RgbaImage {
+        let mut image = RgbaImage::from_pixel(width, height, BACKGROUND);
+        self.draw(render_jobs, &mut image);
+        image
+    }
+
+    fn draw_centered_text(&self, image: &mut RgbaImage, clip: Clip, rect: Clip, text: &str, color: Rgba<u8>) {
+        let width = self.font.line_width(text, FONT_SIZE) as i32;
+        let height = self.font.line_height(FONT_SIZE) as i32;
+        let x = rect.x0 + (rect.x1 - rect.x0 - width) / 2;
+        let y = rect.y0 + (rect.y1 - rect.y0 - height) / 2;
+        self.font.draw_text(image, clip.intersect(rect), x, y, text, color);
+    }
+}
+
+impl RenderBackend<RgbaImage> for SoftwareBackend {
+    fn draw(&mut self, render_jobs: &[RenderJob], image: &mut RgbaImage) {
+        let screen = Clip::new(0, 0, image.width() as i32, image.height() as i32);
+
+        for render_job in render_jobs {
+            match *render_job {
+                RenderJob::Nul => (),
+                RenderJob::Form {hovered, keyboard_focused, disabled, x, y, w, h, ref title, ..} => {
+                    let rect = Clip::new(x, y, w, h);
+
+                    let mut color = FORM;
+                    if hovered {color = FORM_HOVERED;}
+                    if disabled {color = FORM_DISABLED;}
+
+                    let mut border = BLACK;
+                    if keyboard_focused {border = FORM_FOCUSED_BORDER;}
+
+                    fill_rect(image, screen, rect, color);
+                    stroke_rect(image, screen, rect, FORM_BORDER, border);
+                    self.font.draw_text(image, screen.intersect(rect), x + TEXT_PADDING, y + FORM_BORDER, title, BLACK);
+                }
+                RenderJob::Button {pressed, hovered, keyboard_focused, disabled, x, y, w, h, ref text, ..} => {
+                    let rect = Clip::new(x, y, w, h);
+
+                    let mut color = BUTTON;
+                    let mut text_color = BUTTON_TEXT;
+                    if pressed {
+                        color = BUTTON_PRESSED;
+                        text_color = BUTTON_TEXT_PRESSED;
+                    }
+                    else if hovered {color = BUTTON_HOVERED;}
+                    if disabled {
+                        color = BUTTON_DISABLED;
+                        text_color = DISABLED;
+                    }
+
+                    fill_rect(image, screen, rect, color);
+                    if keyboard_focused {
+                        stroke_rect(image, screen, rect, 2, FOCUS_RING);
+                    }
+                    else {
+                        stroke_rect(image, screen, rect, 1, BLACK);
+                    }
+                    self.draw_centered_text(image, screen, rect, text, text_color);
+                }
+                RenderJob::Label {disabled, x, y, ref text, ..} => {
+                    let color = if disabled {DISABLED} else {WHITE};
+                    self.font.draw_text(image, screen, x, y, text, color);
+                }
+                RenderJob::Textbox {hovered, keyboard_focused, disabled, x, y, w, h, ref text, caret, selection, ..} => {
+                    let rect = Clip::new(x, y, w, h);
+
+                    let mut color = TEXTBOX;
+                    if hovered || keyboard_focused {color = TEXTBOX_ACTIVE;}
+                    if disabled {color = DISABLED;}
+
+                    fill_rect(image, screen, rect, color);
+                    stroke_rect(image, screen, rect, 1, BLACK);
+
+                    let clip = screen.intersect(rect);
+                    let text_x = x + TEXT_PADDING;
+                    let text_y = y + (h - self.font.line_height(FONT_SIZE) as i32) / 2;
+                    let offset = |chars: usize| -> i32 {
+                        let prefix: String = text.chars().take(chars).collect();
+                        text_x + self.font.line_width(&prefix, FONT_SIZE) as i32
+                    };
+
+                    if let Some((start, end)) = selection {
+                        fill_rect(image, clip, Clip {x0: offset(start), x1: offset(end), ..rect}, SELECTION);
+                    }
+
+                    self.font.draw_text(image, clip, text_x, text_y, text, BLACK);
+
+                    if keyboard_focused {
+                        let caret_x = offset(caret);
+                        fill_rect(image, clip, Clip {x0: caret_x, x1: caret_x + 1, y0: y + 4, y1: y + h - 4}, BLACK);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/***********************************************************************************
+ *      ImageMismatch
+ *      GoldenError
+ *
+ *      Why a rendered image doesn't match the expected one. count is the
+ *      number of differing pixels and first the top left most of them.
+ ***********************************************************************************/
+#[derive(Debug, Clone, PartialEq)]
+pub enum ImageMismatch {
+    Size { expected: (u32, u32), actual: (u32, u32) },
+    Pixels { count: usize, first: (u32, u32) },
+}
+
+impl fmt::Display for ImageMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ImageMismatch::Size{expected, actual} =>
+                write!(f, "Image is {}x{}, expected {}x{}", actual.0, actual.1, expected.0, expected.1),
+            ImageMismatch::Pixels{count, first} =>
+                write!(f, "{} pixels differ, the first at ({}, {})", count, first.0, first.1),
+        }
+    }
+}
+
+impl Error for ImageMismatch {}
+
+#[derive(Debug)]
+pub enum GoldenError {
+    Io(String),
+    Mismatch(ImageMismatch),
+}
+
+impl fmt::Display for GoldenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            GoldenError::Io(ref message) => write!(f, "Couldn't access the golden image: {}", message),
+            GoldenError::Mismatch(ref e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for GoldenError {}
+
+impl From<ImageMismatch> for GoldenError {
+    fn from(e: ImageMismatch) -> GoldenError {
+        GoldenError::Mismatch(e)
+    }
+}
+
+impl From<image::ImageError> for GoldenError {
+    fn from(e: image::ImageError) -> GoldenError {
+        GoldenError::Io(format!("{}", e))
+    }
+}
+
+/***********************************************************************************
+ *      compare_images
+ *
+ *      Compares two images pixel by pixel. Channels may differ by tolerance
+ *      to allow for small differences in anti-aliasing.
+ ***********************************************************************************/
+pub fn compare_images(actual: &RgbaImage, expected: &RgbaImage, tolerance: u8) -> Result<(), ImageMismatch> {
+    if actual.dimensions() != expected.dimensions() {
+        return Err(ImageMismatch::Size{expected: expected.dimensions(), actual: actual.dimensions()});
+    }
+
+    let mut count = 0;
+    let mut first = (0, 0);
+    for (x, y, pixel) in actual.enumerate_pixels() {
+        let other = expected.get_pixel(x, y);
+        let differs = pixel.0.iter().zip(other.0.iter())
+            .any(|(a, b)| (*a as i32 - *b as i32).abs() > tolerance as i32);
+
+        if differs {
+            if count == 0 { first = (x, y); }
+            count = count + 1;
+        }
+    }
+
+    if count > 0 {
+        return Err(ImageMismatch::Pixels{count: count, first: first});
+    }
+    Ok(())
+}
+
+/***********************************************************************************
+ *      check_golden
+ *
+ *      Compares a rendered image with the golden image stored at path. With
+ *      the UPDATE_GOLDEN environment variable set the golden image is
+ *      written instead, to accept an intended change in the rendering.
+ ***********************************************************************************/
+pub fn check_golden<P: AsRef<Path>>(actual: &RgbaImage, path: P, tolerance: u8) -> Result<(), GoldenError> {
+    let path = path.as_ref();
+
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        actual.save(path)?;
+        return Ok(());
+    }
+
+    let expected = image::open(path)?.to_rgba8();
+    compare_images(actual, &expected, tolerance)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gui::{UI, Parent, new_form, new_button, new_label};
+
+    fn render_menu(font: SoftwareFont) -> RgbaImage {
+        let mut ui = UI::new(200, 150);
+        ui.set_text_measurer(Box::new(font.clone()));
+        let form = ui.add_widget(Parent::Root, new_form(10, 10, 180, 130, "Menu")).unwrap();
+        ui.add_widget(form, new_label(10, 30, "Gold")).unwrap();
+        ui.add_widget(form, new_button(10, 70, 120, 40, "Quit")).unwrap();
+
+        SoftwareBackend::new(font).render_to_image(&ui.render(), 200, 150)
+    }
+
+    #[test]
+    fn test_software_rendering() {
+        let image = render_menu(SoftwareFont::dogma().unwrap());
+
+        assert_eq!(*image.get_pixel(5, 5), BACKGROUND);
+        assert_eq!(*image.get_pixel(11, 11), BLACK);
+        assert_eq!(*image.get_pixel(100, 135), FORM);
+        assert_eq!(*image.get_pixel(22, 82), BUTTON);
+        assert_eq!(*image.get_pixel(20, 80), BLACK);
+
+        //The label and the button text leave some pixels of their color
+        let has_text = |x0: u32, y0: u32, x1: u32, y1: u32, color: Rgba<u8>| {
+            (y0 .. y1).any(|y| (x0 .. x1).any(|x| *image.get_pixel(x, y) == color))
+        };
+        assert!(has_text(20, 40, 80, 60, WHITE));
+        assert!(has_text(21, 81, 139, 119, BUTTON_TEXT));
+    }
+
+    #[test]
+    fn test_golden_images() {
+        let image = render_menu(SoftwareFont::dogma().unwrap());
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden/software_menu.png");
+        check_golden(&image, path, 8).unwrap();
+
+        let mut changed = image.clone();
+        changed.put_pixel(3, 4, WHITE);
+        changed.put_pixel(7, 4, WHITE);
+        assert_eq!(compare_images(&changed, &image, 0), Err(ImageMismatch::Pixels{count: 2, first: (3, 4)}));
+        assert_eq!(compare_images(&changed, &RgbaImage::new(10, 10), 0),
+                   Err(ImageMismatch::Size{expected: (10, 10), actual: (200, 150)}));
+    }
+}
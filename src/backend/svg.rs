@@ -0,0 +1,253 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+use image::Rgba;
+use gui::{UI, Parent, RenderJob, WidgetHandle, FONT_SIZE, FORM_BORDER};
+use backend::RenderBackend;
+use backend::palette::{BACKGROUND, BLACK, WHITE, FORM, FORM_HOVERED, FORM_DISABLED, FORM_FOCUSED_BORDER,
+                       BUTTON, BUTTON_HOVERED, BUTTON_PRESSED, BUTTON_DISABLED, BUTTON_TEXT, BUTTON_TEXT_PRESSED,
+                       FOCUS_RING, TEXTBOX, TEXTBOX_ACTIVE, SELECTION, DISABLED};
+
+const TEXT_PADDING: i32 = 6;
+
+/***********************************************************************************
+ *      SvgDocument
+ *
+ *      The elements drawn by the svg backend. Formats as a complete svg
+ *      document of the given size.
+ ***********************************************************************************/
+#[derive(Clone, Debug, PartialEq)]
+pub struct SvgDocument {
+    width: u32,
+    height: u32,
+    elements: Vec<String>,
+}
+
+impl SvgDocument {
+    pub fn new(width: u32, height: u32) -> SvgDocument {
+        SvgDocument {
+            width: width,
+            height: height,
+            elements: Vec::new(),
+        }
+    }
+
+    pub fn elements(&self) -> &[String] {
+        &self.elements
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut file = File::create(path)?;
+        write!(file, "{}", self)
+    }
+}
+
+impl fmt::Display for SvgDocument {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" viewBox=\"0 0 {0} {1}\">", self.width, self.height)?;
+        writeln!(f, "<rect width=\"100%\" height=\"100%\" fill=\"{}\"/>", hex(BACKGROUND))?;
+        for element in &self.elements {
+            writeln!(f, "{}", element)?;
+        }
+        writeln!(f, "</svg>")
+    }
+}
+
+/***********************************************************************************
+ *      SvgBackend
+ *
+ *      Draws RenderJobs as svg elements, to look at menu layouts in a
+ *      browser. Every widget is a group with the id widget-<handle>, or
+ *      <name>-<handle> for named widgets. Text is drawn with the browser's
+ *      fonts, so its size is only close to the other backends.
+ ***********************************************************************************/
+pub struct SvgBackend {
+    names: HashMap<WidgetHandle, String>,
+}
+
+impl SvgBackend {
+    pub fn new() -> SvgBackend {
+        SvgBackend {
+            names: HashMap::new(),
+        }
+    }
+
+/***********************************************************************************
+ *      SvgBackend::name_widgets
+ *
+ *      Takes the names of all widgets of the UI for the element ids.
+ ***********************************************************************************/
+    pub fn name_widgets(&mut self, ui: &UI) {
+        self.names.clear();
+        for root in ui.children(Parent::Root) {
+            for handle in Some(root).into_iter().chain(ui.descendants(root)) {
+                if let Some(name) = ui.name(handle) {
+                    self.names.insert(handle, name.to_owned());
+                }
+            }
+        }
+    }
+
+    pub fn render_to_svg(&mut self, render_jobs: &[RenderJob], width: u32, height: u32) -> SvgDocument {
+        let mut document = SvgDocument::new(width, height);
+        self.draw(render_jobs, &mut document);
+        document
+    }
+
+    fn element_id(&self, handle: WidgetHandle) -> String {
+        match self.names.get(&handle) {
+            Some(name) => format!("{}-{}", escape(name), handle),
+            None => format!("widget-{}", handle)
+        }
+    }
+}
+
+impl RenderBackend<SvgDocument> for SvgBackend {
+    fn draw(&mut self, render_jobs: &[RenderJob], document: &mut SvgDocument) {
+        for render_job in render_jobs {
+            let id = match render_job.handle() {
+                Some(handle) => self.element_id(handle),
+                None => continue
+            };
+
+            let mut group = format!("<g id=\"{}\">", id);
+
+            match *render_job {
+                RenderJob::Nul => (),
+                RenderJob::Form {hovered, keyboard_focused, disabled, x, y, w, h, ref title, ..} => {
+                    let mut color = FORM;
+                    if hovered {color = FORM_HOVERED;}
+                    if disabled {color = FORM_DISABLED;}
+
+                    let mut border = BLACK;
+                    if keyboard_focused {border = FORM_FOCUSED_BORDER;}
+
+                    group.push_str(&rect(x, y, w, h, color, border, FORM_BORDER));
+                    group.push_str(&text(x + TEXT_PADDING, y + FORM_BORDER, "", title, BLACK));
+                }
+                RenderJob::Button {pressed, hovered, keyboard_focused, disabled, x, y, w, h, text: ref label, ..} => {
+                    let mut color = BUTTON;
+                    let mut text_color = BUTTON_TEXT;
+                    if pressed {
+                        color = BUTTON_PRESSED;
+                        text_color = BUTTON_TEXT_PRESSED;
+                    }
+                    else if hovered {color = BUTTON_HOVERED;}
+                    if disabled {
+                        color = BUTTON_DISABLED;
+                        text_color = DISABLED;
+                    }
+
+                    if keyboard_focused {
+                        group.push_str(&rect(x, y, w, h, color, FOCUS_RING, 2));
+                    }
+                    else {
+                        group.push_str(&rect(x, y, w, h, color, BLACK, 1));
+                    }
+                    group.push_str(&text(x + w / 2, y + h / 2, " text-anchor=\"middle\" dominant-baseline=\"central\"", label, text_color));
+                }
+                RenderJob::Label {disabled, x, y, text: ref label, ..} => {
+                    let color = if disabled {DISABLED} else {WHITE};
+                    group.push_str(&text(x, y, "", label, color));
+                }
+                RenderJob::Textbox {hovered, keyboard_focused, disabled, x, y, w, h, text: ref content, caret, selection, ..} => {
+                    let mut color = TEXTBOX;
+                    if hovered || keyboard_focused {color = TEXTBOX_ACTIVE;}
+                    if disabled {color = DISABLED;}
+
+                    group.push_str(&rect(x, y, w, h, color, BLACK, 1));
+
+                    //Positions inside the text are only known in characters, the selection
+                    //and the caret are marked with tspans instead of pixel offsets
+                    let part = |from: usize, to: usize| -> String {
+                        escape(&content.chars().skip(from).take(to - from).collect::<String>())
+                    };
+                    let length = content.chars().count();
+                    let line = match selection {
+                        Some((start, end)) => format!("{}<tspan fill=\"{}\">{}</tspan>{}",
+                            part(0, start), hex(SELECTION), part(start, end), part(end, length)),
+                        None if keyboard_focused => format!("{}<tspan>|</tspan>{}", part(0, caret), part(caret, length)),
+                        None => escape(content)
+                    };
+
+                    group.push_str(&format!("<text x=\"{}\" y=\"{}\" font-size=\"{}\" dominant-baseline=\"central\" fill=\"{}\">{}</text>",
+                        x + TEXT_PADDING, y + h / 2, FONT_SIZE, hex(BLACK), line));
+                }
+            }
+
+            group.push_str("</g>");
+            document.elements.push(group);
+        }
+    }
+}
+
+fn rect(x: i32, y: i32, w: i32, h: i32, fill: Rgba<u8>, stroke: Rgba<u8>, stroke_width: i32) -> String {
+    format!("<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\" stroke=\"{}\" stroke-width=\"{}\"/>",
+        x, y, w, h, hex(fill), hex(stroke), stroke_width)
+}
+
+//Text is placed by its top left corner unless other attributes are given
+fn text(x: i32, y: i32, attributes: &str, content: &str, fill: Rgba<u8>) -> String {
+    let attributes = if attributes.is_empty() {" dominant-baseline=\"hanging\""} else {attributes};
+    format!("<text x=\"{}\" y=\"{}\" font-size=\"{}\"{} fill=\"{}\">{}</text>",
+        x, y, FONT_SIZE, attributes, hex(fill), escape(content))
+}
+
+//Colors are written as #rrggbb, svg has no use for the alpha of the palette
+fn hex(color: Rgba<u8>) -> String {
+    format!("#{:02x}{:02x}{:02x}", color[0], color[1], color[2])
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c)
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gui::{UI, Parent, new_form, new_button, new_label};
+
+    #[test]
+    fn test_svg_export() {
+        let mut ui = UI::new(200, 150);
+        let form = ui.add_widget(Parent::Root, new_form(10, 10, 180, 130, "Fish & Chips")).unwrap();
+        let label = ui.add_widget(form, new_label(10, 30, "Gold")).unwrap();
+        let quit = ui.add_widget(form, new_button(10, 70, 120, 40, "Quit")).unwrap();
+        ui.set_name(form, "menu");
+        ui.set_name(quit, "quit");
+
+        let mut backend = SvgBackend::new();
+        backend.name_widgets(&ui);
+        let document = backend.render_to_svg(&ui.render(), 200, 150);
+
+        let elements = document.elements();
+        assert_eq!(elements.len(), 3);
+        assert_eq!(elements[0], format!(concat!(
+            "<g id=\"menu-{}\">",
+            "<rect x=\"10\" y=\"10\" width=\"180\" height=\"130\" fill=\"#939393\" stroke=\"#000000\" stroke-width=\"3\"/>",
+            "<text x=\"16\" y=\"13\" font-size=\"18\" dominant-baseline=\"hanging\" fill=\"#000000\">Fish &amp; Chips</text>",
+            "</g>"), form));
+        assert_eq!(elements[1], format!(concat!(
+            "<g id=\"widget-{}\">",
+            "<text x=\"20\" y=\"40\" font-size=\"18\" dominant-baseline=\"hanging\" fill=\"#ffffff\">Gold</text>",
+            "</g>"), label));
+        assert!(elements[2].starts_with(&format!("<g id=\"quit-{}\"><rect x=\"20\" y=\"80\" width=\"120\" height=\"40\"", quit)));
+
+        let svg = document.to_string();
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"200\" height=\"150\""));
+        assert!(svg.ends_with("</svg>\n"));
+    }
+}
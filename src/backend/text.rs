@@ -0,0 +1,367 @@
+use std::fmt;
+use gui::RenderJob;
+use backend::{RenderBackend, text_with_caret};
+
+/***********************************************************************************
+ *      TextGrid
+ *
+ *      A grid of characters the text backend draws onto, row by row.
+ *      Formats as one line per row.
+ ***********************************************************************************/
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextGrid {
+    columns: u32,
+    rows: u32,
+    cells: Vec<char>,
+}
+
+impl TextGrid {
+    pub fn new(columns: u32, rows: u32) -> TextGrid {
+        TextGrid {
+            columns: columns,
+            rows: rows,
+            cells: vec![' '; (columns * rows) as usize],
+        }
+    }
+
+    pub fn columns(&self) -> u32 {
+        self.columns
+    }
+
+    pub fn rows(&self) -> u32 {
+        self.rows
+    }
+
+    pub fn get(&self, column: u32, row: u32) -> Option<char> {
+        if column >= self.columns || row >= self.rows {return None;}
+        Some(self.cells[(row * self.columns + column) as usize])
+    }
+
+    //Writes outside of the grid are dropped
+    pub fn set(&mut self, column: i32, row: i32, c: char) {
+        if column < 0 || row < 0 || column >= self.columns as i32 || row >= self.rows as i32 {return;}
+        self.cells[(row * self.columns as i32 + column) as usize] = c;
+    }
+
+    pub fn put_str(&mut self, column: i32, row: i32, text: &str) {
+        for (i, c) in text.chars().enumerate() {
+            self.set(column + i as i32, row, c);
+        }
+    }
+
+    pub fn line(&self, row: u32) -> String {
+        let start = (row * self.columns) as usize;
+        self.cells[start .. start + self.columns as usize].iter().collect()
+    }
+
+    pub fn clear(&mut self) {
+        for cell in self.cells.iter_mut() {
+            *cell = ' ';
+        }
+    }
+}
+
+impl fmt::Display for TextGrid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for row in 0 .. self.rows {
+            writeln!(f, "{}", self.line(row))?;
+        }
+        Ok(())
+    }
+}
+
+/***********************************************************************************
+ *      TextBackend
+ *
+ *      Draws RenderJobs as characters. Every cell of the grid covers
+ *      cell_width x cell_height pixels of the UI. Forms are box-drawing
+ *      frames with their title, buttons look like [ OK. ] and labels are
+ *      plain text. A focused button is drawn as [>OK.<].
+ ***********************************************************************************/
+pub struct TextBackend {
+    cell_width: i32,
+    cell_height: i32,
+}
+
+impl TextBackend {
+    pub fn new(cell_width: u32, cell_height: u32) -> TextBackend {
+        TextBackend {
+            cell_width: cell_width as i32,
+            cell_height: cell_height as i32,
+        }
+    }
+
+/***********************************************************************************
+ *      TextBackend::cell_at
+ *      TextBackend::cell_center
+ *
+ *      Converts between UI pixels and grid cells.
+ ***********************************************************************************/
+    pub fn cell_at(&self, x: i32, y: i32) -> (i32, i32) {
+        (floor_div(x, self.cell_width), floor_div(y, self.cell_height))
+    }
+
+    pub fn cell_center(&self, column: i32, row: i32) -> (i32, i32) {
+        (column * self.cell_width + self.cell_width / 2, row * self.cell_height + self.cell_height / 2)
+    }
+
+    pub fn render_to_grid(&mut self, render_jobs: &[RenderJob], columns: u32, rows: u32) -> TextGrid {
+        let mut grid = TextGrid::new(columns, rows);
+        self.draw(render_jobs, &mut grid);
+        grid
+    }
+
+    //First and last cell covered by a rect, a rect covers at least one cell
+    fn cells(&self, x: i32, y: i32, w: i32, h: i32) -> (i32, i32, i32, i32) {
+        let (left, top) = self.cell_at(x, y);
+        let (right, bottom) = self.cell_at(x + w - 1, y + h - 1);
+        (left, top, cmp_max(left, right), cmp_max(top, bottom))
+    }
+
+    fn draw_form(&self, grid: &mut TextGrid, x: i32, y: i32, w: i32, h: i32, title: &str, keyboard_focused: bool) {
+        let (left, top, right, bottom) = self.cells(x, y, w, h);
+
+        //Forms hide what is below them
+        for row in top .. bottom + 1 {
+            for column in left .. right + 1 {
+                grid.set(column, row, ' ');
+            }
+        }
+
+        let (horizontal, vertical, corners) = if keyboard_focused {
+            ('═', '║', ['╔', '╗', '╚', '╝'])
+        } else {
+            ('─', '│', ['┌', '┐', '└', '┘'])
+        };
+
+        for column in left + 1 .. right {
+            grid.set(column, top, horizontal);
+            grid.set(column, bottom, horizontal);
+        }
+        for row in top + 1 .. bottom {
+            grid.set(left, row, vertical);
+            grid.set(right, row, vertical);
+        }
+        grid.set(left, top, corners[0]);
+        grid.set(right, top, corners[1]);
+        grid.set(left, bottom, corners[2]);
+        grid.set(right, bottom, corners[3]);
+
+        //The title goes into the top edge as long as it fits
+        let room = (right - left - 3) as usize;
+        if right - left > 3 && !title.is_empty() {
+            let title: String = title.chars().take(room).collect();
+            grid.put_str(left + 1, top, &format!(" {} ", title));
+            grid.set(right, top, corners[1]);
+        }
+    }
+}
+
+impl RenderBackend<TextGrid> for TextBackend {
+    fn draw(&mut self, render_jobs: &[RenderJob], grid: &mut TextGrid) {
+        for render_job in render_jobs {
+            match *render_job {
+                RenderJob::Nul => (),
+                RenderJob::Form {keyboard_focused, x, y, w, h, ref title, ..} => {
+                    self.draw_form(grid, x, y, w, h, title, keyboard_focused);
+                }
+                RenderJob::Button {keyboard_focused, disabled, x, y, w, h, ref text, ..} => {
+                    let (left, top, _, bottom) = self.cells(x, y, w, h);
+                    let button = if disabled {
+                        format!("( {} )", text)
+                    } else if keyboard_focused {
+                        format!("[>{}<]", text)
+                    } else {
+                        format!("[ {} ]", text)
+                    };
+                    grid.put_str(left, (top + bottom) / 2, &button);
+                }
+                RenderJob::Label {x, y, ref text, ..} => {
+                    let (column, row) = self.cell_at(x, y);
+                    grid.put_str(column, row, text);
+                }
+                RenderJob::Textbox {keyboard_focused, x, y, w, h, ref text, caret, ..} => {
+                    let (left, top, right, bottom) = self.cells(x, y, w, h);
+                    let row = (top + bottom) / 2;
+
+                    let field = text_with_caret(text, caret, keyboard_focused);
+
+                    grid.set(left, row, '[');
+                    for column in left + 1 .. right {
+                        grid.set(column, row, '_');
+                    }
+                    let room = cmp_max(0, right - left - 1) as usize;
+                    grid.put_str(left + 1, row, &field.chars().take(room).collect::<String>());
+                    grid.set(right, row, ']');
+                }
+            }
+        }
+    }
+}
+
+fn floor_div(a: i32, b: i32) -> i32 {
+    let d = a / b;
+    if a % b != 0 && (a < 0) != (b < 0) { d - 1 } else { d }
+}
+
+fn cmp_max(a: i32, b: i32) -> i32 {
+    if a > b { a } else { b }
+}
+
+/***********************************************************************************
+ *      terminal
+ *
+ *      Runs a UI in a terminal with the text backend, to try out menus over
+ *      SSH. Needs the crossterm feature.
+ ***********************************************************************************/
+#[cfg(feature = "crossterm")]
+pub mod terminal {
+    use crossterm::{cursor, event, terminal, style};
+    use crossterm::{ExecutableCommand, QueueableCommand};
+    use std::io::{self, Write};
+    use gui::{UI, Key, Modifiers, WidgetEvent};
+    use super::{TextBackend, TextGrid};
+
+    pub fn translate_key(code: event::KeyCode, modifiers: event::KeyModifiers) -> (Key, Modifiers) {
+        let mut translated = Modifiers {
+            shift: modifiers.contains(event::KeyModifiers::SHIFT),
+            ctrl: modifiers.contains(event::KeyModifiers::CONTROL),
+            alt: modifiers.contains(event::KeyModifiers::ALT),
+            logo: modifiers.contains(event::KeyModifiers::SUPER),
+        };
+
+        let key = match code {
+            event::KeyCode::Backspace => Key::Backspace,
+            event::KeyCode::Delete => Key::Delete,
+            event::KeyCode::Enter => Key::Enter,
+            event::KeyCode::Esc => Key::Escape,
+            event::KeyCode::Tab => Key::Tab,
+            event::KeyCode::BackTab => { translated.shift = true; Key::Tab }
+            event::KeyCode::Left => Key::Left,
+            event::KeyCode::Right => Key::Right,
+            event::KeyCode::Up => Key::Up,
+            event::KeyCode::Down => Key::Down,
+            event::KeyCode::Home => Key::Home,
+            event::KeyCode::End => Key::End,
+            event::KeyCode::PageUp => Key::PageUp,
+            event::KeyCode::PageDown => Key::PageDown,
+            event::KeyCode::Char(' ') => Key::Space,
+            event::KeyCode::Char(c) => Key::Character(c.to_ascii_lowercase()),
+            event::KeyCode::F(n) => Key::Function(n),
+            _ => Key::Unknown
+        };
+
+        (key, translated)
+    }
+
+/***********************************************************************************
+ *      terminal::run
+ *
+ *      Draws the UI into the terminal and feeds it the terminal's key and
+ *      mouse input until Ctrl+C is pressed or on_event returns false. Every
+ *      event the UI pushes is passed to on_event.
+ ***********************************************************************************/
+    pub fn run<F>(ui: &mut UI, backend: &mut TextBackend, mut on_event: F) -> io::Result<()>
+        where F: FnMut(&mut UI, WidgetEvent) -> bool
+    {
+        let mut out = io::stdout();
+        terminal::enable_raw_mode()?;
+        out.execute(terminal::EnterAlternateScreen)?;
+        out.execute(event::EnableMouseCapture)?;
+        out.execute(cursor::Hide)?;
+
+        let result = event_loop(ui, backend, &mut on_event, &mut out);
+
+        //Give the terminal back even if the loop failed
+        out.execute(cursor::Show)?;
+        out.execute(event::DisableMouseCapture)?;
+        out.execute(terminal::LeaveAlternateScreen)?;
+        terminal::disable_raw_mode()?;
+        result
+    }
+
+    fn event_loop<F>(ui: &mut UI, backend: &mut TextBackend, on_event: &mut F, out: &mut io::Stdout) -> io::Result<()>
+        where F: FnMut(&mut UI, WidgetEvent) -> bool
+    {
+        let mut mouse = (0, 0);
+
+        loop {
+            let (columns, rows) = terminal::size()?;
+            let grid = backend.render_to_grid(&ui.render(), columns as u32, rows as u32);
+            draw(&grid, out)?;
+
+            match event::read()? {
+                event::Event::Key(key) if key.kind != event::KeyEventKind::Release => {
+                    let (translated, modifiers) = translate_key(key.code, key.modifiers);
+                    if modifiers.ctrl && translated == Key::Character('c') {return Ok(());}
+
+                    ui.keydown(translated, modifiers);
+                    if let event::KeyCode::Char(c) = key.code {
+                        //Ctrl shortcuts are handled by keydown
+                        if !modifiers.ctrl && !modifiers.alt { ui.text_input(&c.to_string()); }
+                    }
+                }
+                event::Event::Mouse(m) => {
+                    let (x, y) = backend.cell_center(m.column as i32, m.row as i32);
+                    ui.mousemove(mouse.0, mouse.1, x, y);
+                    mouse = (x, y);
+
+                    match m.kind {
+                        event::MouseEventKind::Down(event::MouseButton::Left) => ui.mousedown(),
+                        event::MouseEventKind::Up(event::MouseButton::Left) => ui.mouseup(),
+                        _ => ()
+                    }
+                }
+                _ => ()
+            }
+
+            let events: Vec<WidgetEvent> = ui.drain_events().collect();
+            for event in events {
+                if !on_event(ui, event) {return Ok(());}
+            }
+        }
+    }
+
+    fn draw(grid: &TextGrid, out: &mut io::Stdout) -> io::Result<()> {
+        for row in 0 .. grid.rows() {
+            out.queue(cursor::MoveTo(0, row as u16))?;
+            out.queue(style::Print(grid.line(row)))?;
+        }
+        out.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gui::{UI, Parent, new_form, new_button, new_label, new_textbox};
+
+    #[test]
+    fn test_text_rendering() {
+        let mut ui = UI::new(320, 160);
+        let form = ui.add_widget(Parent::Root, new_form(8, 16, 256, 112, "Menu")).unwrap();
+        ui.add_widget(form, new_label(16, 16, "Gold: 120")).unwrap();
+        let ok = ui.add_widget(form, new_button(16, 32, 80, 16, "OK.")).unwrap();
+        ui.add_widget(form, new_textbox(16, 64, 96, 16, "Hero")).unwrap();
+        ui.set_keyboard_focus(Some(ok));
+
+        //The form holding the focus gets the double frame
+        let mut backend = TextBackend::new(8, 16);
+        let grid = backend.render_to_grid(&ui.render(), 34, 9);
+
+        assert_eq!(grid.to_string(), concat!(
+            "                                  \n",
+            " ╔ Menu ════════════════════════╗ \n",
+            " ║ Gold: 120                    ║ \n",
+            " ║ [>OK.<]                      ║ \n",
+            " ║                              ║ \n",
+            " ║ [Hero______]                 ║ \n",
+            " ║                              ║ \n",
+            " ╚══════════════════════════════╝ \n",
+            "                                  \n"));
+
+        assert_eq!(backend.cell_at(17, 33), (2, 2));
+        assert_eq!(backend.cell_at(-1, -1), (-1, -1));
+        assert_eq!(backend.cell_center(2, 2), (20, 40));
+    }
+}
@@ -4,7 +4,12 @@ extern crate std;
 extern crate cgmath;
 
 use layout;
-use conrod;
+use backend::TextMeasurer;
+use std::error::Error;
+use std::fmt;
+use std::collections::VecDeque;
+use std::collections::HashMap;
+use std::collections::HashSet;
 
 #[derive(Copy, Clone)]
 pub struct Vertex {
@@ -50,6 +55,8 @@ pub enum Widget {
     Textbox {   position: Position,
                 size: Size,
                 text: String,
+                caret: usize,
+                selection: Option<usize>,
                 },
 }
 
@@ -57,7 +64,9 @@ pub enum RenderJob {
     Nul,
 
     Form {  index:  WidgetHandle,
-            focus:  bool,
+            hovered: bool,
+            keyboard_focused: bool,
+            disabled: bool,
             x:      i32,
             y:      i32,
             w:      i32,
@@ -67,7 +76,9 @@ pub enum RenderJob {
 
     Button { index: WidgetHandle,
              pressed: bool,
-             focus: bool,
+             hovered: bool,
+             keyboard_focused: bool,
+             disabled: bool,
              x:     i32,
              y:     i32,
              w:     i32,
@@ -77,22 +88,129 @@ pub enum RenderJob {
 
     Label { 
             index:  WidgetHandle, 
+            disabled: bool,
             x:      i32,
             y:      i32,
             text:   String, 
     },
 
+    Textbox { index:     WidgetHandle,
+              hovered:   bool,
+              keyboard_focused: bool,
+              disabled:  bool,
+              x:         i32,
+              y:         i32,
+              w:         i32,
+              h:         i32,
+              text:      String,
+              caret:     usize,
+              selection: Option<(usize, usize)>
+    },
+
 }
 
+#[derive(Clone, Debug, PartialEq)]
 pub enum WidgetEvent {
-    ButtonClicked(i32),
+    ButtonClicked(WidgetHandle),
+    TextChanged(WidgetHandle),
+    TextSubmitted(WidgetHandle),
+    KeyPressed(WidgetHandle, Key, Modifiers),
+    KeyReleased(WidgetHandle, Key, Modifiers),
+    FocusGained(WidgetHandle),
+    FocusLost(WidgetHandle),
+    CloseRequested(WidgetHandle),
+}
+
+pub type Callback = Box<FnMut(&mut UI, &WidgetEvent)>;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+enum CallbackKind {
+    Click,
+    Change,
+    Close
+}
+
+fn callback_target(event: &WidgetEvent) -> Option<(WidgetHandle, CallbackKind)> {
+    match *event {
+        WidgetEvent::ButtonClicked(handle) => Some((handle, CallbackKind::Click)),
+        WidgetEvent::TextChanged(handle) => Some((handle, CallbackKind::Change)),
+        WidgetEvent::CloseRequested(handle) => Some((handle, CallbackKind::Close)),
+        _ => None
+    }
+}
+
+//Key codes delivered by the host to UI::keydown and UI::keyup.
+//Letter and digit keys are reported as lowercase Character keys.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Key {
+    Backspace,
+    Delete,
+    Enter,
+    Escape,
+    Tab,
+    Space,
+    Left,
+    Right,
+    Up,
+    Down,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    Character(char),
+    Function(u8),
+    Unknown
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Default)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub ctrl: bool,
+    pub alt: bool,
+    pub logo: bool,
+}
+
+impl Modifiers {
+    pub fn none() -> Modifiers {
+        Modifiers::default()
+    }
+
+    pub fn shift() -> Modifiers {
+        Modifiers { shift: true, .. Modifiers::default() }
+    }
+}
+
+//Caret positions and selections are counted in characters, not bytes
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum CaretMotion {
+    Left,
+    Right,
+    Home,
+    End
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TextEdit {
+    Insert(String),
+    Backspace,
+    Delete,
+    Move { motion: CaretMotion, select: bool },
+    SelectAll,
+    Submit
+}
+
+#[derive(PartialEq)]
+enum TextEditResult {
+    Unchanged,
+    Changed,
+    Submitted
 }
 
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum Layout {
     NoLayout,
     Vertical,
     Horizontal
-    
 }
 
 pub fn new_form(ix: i32, iy: i32, iw: i32, ih: i32, title: &str) -> Widget {
@@ -119,6 +237,16 @@ pub fn new_button(ix: i32, iy: i32, iw: i32, ih: i32, itext: &str) -> Widget {
     }
 }
 
+pub fn new_textbox(ix: i32, iy: i32, iw: i32, ih: i32, itext: &str) -> Widget {
+    Widget::Textbox {
+        position: Position {x: ix, y: iy},
+        size: Size {w: iw, h: ih},
+        text: itext.to_string(),
+        caret: itext.chars().count(),
+        selection: None,
+    }
+}
+
 fn new_rect(pos: &Position, size: &Size) -> Rect {
     Rect {
         x: pos.x,
@@ -148,6 +276,20 @@ fn get_widget_rect(widget: &Widget) -> Option<Rect> {
     }
 }
 
+//The part of a widget its children are laid out in, relative to the widget
+fn layout_area(widget: &Widget) -> Rect {
+    match (widget, get_widget_rect(widget)) {
+        (&Widget::Form{..}, Some(rect)) => Rect {
+            x: FORM_BORDER,
+            y: TITLE_BAR_HEIGHT,
+            w: std::cmp::max(0, rect.w - 2 * FORM_BORDER),
+            h: std::cmp::max(0, rect.h - TITLE_BAR_HEIGHT - FORM_BORDER),
+        },
+        (_, Some(rect)) => Rect {x: 0, y: 0, w: std::cmp::max(0, rect.w), h: std::cmp::max(0, rect.h)},
+        (_, None) => Rect {x: 0, y: 0, w: 0, h: 0}
+    }
+}
+
 fn set_widget_position(widget: &mut Widget, x: i32, y: i32) {
     match *widget {
         Widget::Empty => (),
@@ -186,6 +328,140 @@ fn widget_event_mousedown(widget: &mut Widget) {
 
 }
 
+fn byte_offset(text: &str, char_index: usize) -> usize {
+    match text.char_indices().nth(char_index) {
+        Some((offset, _)) => offset,
+        None => text.len()
+    }
+}
+
+fn selection_range(caret: usize, selection: Option<usize>) -> Option<(usize, usize)> {
+    match selection {
+        Some(anchor) if anchor < caret => Some((anchor, caret)),
+        Some(anchor) if anchor > caret => Some((caret, anchor)),
+        _ => None
+    }
+}
+
+fn textbox_delete_selection(text: &mut String, caret: &mut usize, selection: &mut Option<usize>) -> bool {
+    let range = selection_range(*caret, *selection);
+    *selection = None;
+
+    match range {
+        Some((start, end)) => {
+            let (start_byte, end_byte) = (byte_offset(text, start), byte_offset(text, end));
+            text.drain(start_byte .. end_byte);
+            *caret = start;
+            true
+        }
+        None => false
+    }
+}
+
+/***********************************************************************************
+ *      textbox_edit
+ *      
+ *      Applies a single editing operation to the text, caret and selection
+ *      anchor of a Textbox widget.
+ ***********************************************************************************/
+fn textbox_edit(text: &mut String, caret: &mut usize, selection: &mut Option<usize>, edit: TextEdit) -> TextEditResult {
+    let len = text.chars().count();
+
+    match edit {
+        TextEdit::Insert(ref input) => {
+            //Single line text box - control characters like newlines are dropped
+            let input: String = input.chars().filter(|c| !c.is_control()).collect();
+            let deleted = textbox_delete_selection(text, caret, selection);
+            if input.is_empty() {
+                return if deleted {TextEditResult::Changed} else {TextEditResult::Unchanged};
+            }
+
+            let offset = byte_offset(text, *caret);
+            text.insert_str(offset, &input);
+            *caret = *caret + input.chars().count();
+            TextEditResult::Changed
+        }
+        TextEdit::Backspace => {
+            if textbox_delete_selection(text, caret, selection) { return TextEditResult::Changed; }
+            if *caret == 0 { return TextEditResult::Unchanged; }
+
+            *caret = *caret - 1;
+            let offset = byte_offset(text, *caret);
+            text.remove(offset);
+            TextEditResult::Changed
+        }
+        TextEdit::Delete => {
+            if textbox_delete_selection(text, caret, selection) { return TextEditResult::Changed; }
+            if *caret >= len { return TextEditResult::Unchanged; }
+
+            let offset = byte_offset(text, *caret);
+            text.remove(offset);
+            TextEditResult::Changed
+        }
+        TextEdit::Move{motion, select} => {
+            let range = selection_range(*caret, *selection);
+
+            if select {
+                if selection.is_none() { *selection = Some(*caret); }
+            }
+            else {
+                *selection = None;
+                //Moving left or right without shift collapses the selection to its edge
+                if let Some((start, end)) = range {
+                    match motion {
+                        CaretMotion::Left => { *caret = start; return TextEditResult::Unchanged; }
+                        CaretMotion::Right => { *caret = end; return TextEditResult::Unchanged; }
+                        _ => ()
+                    }
+                }
+            }
+
+            *caret = match motion {
+                CaretMotion::Left => if *caret > 0 {*caret - 1} else {0},
+                CaretMotion::Right => if *caret < len {*caret + 1} else {len},
+                CaretMotion::Home => 0,
+                CaretMotion::End => len,
+            };
+            TextEditResult::Unchanged
+        }
+        TextEdit::SelectAll => {
+            *selection = Some(0);
+            *caret = len;
+            TextEditResult::Unchanged
+        }
+        TextEdit::Submit => TextEditResult::Submitted
+    }
+}
+
+/***********************************************************************************
+ *      textbox_key_edit
+ *      
+ *      Translates a key press into an editing operation for a focused Textbox.
+ *      Keys that a text box doesn't use return None and bubble up.
+ ***********************************************************************************/
+fn textbox_key_edit(key: Key, modifiers: Modifiers) -> Option<TextEdit> {
+    let select = modifiers.shift;
+    match key {
+        Key::Backspace => Some(TextEdit::Backspace),
+        Key::Delete => Some(TextEdit::Delete),
+        Key::Enter => Some(TextEdit::Submit),
+        Key::Left => Some(TextEdit::Move{motion: CaretMotion::Left, select: select}),
+        Key::Right => Some(TextEdit::Move{motion: CaretMotion::Right, select: select}),
+        Key::Home => Some(TextEdit::Move{motion: CaretMotion::Home, select: select}),
+        Key::End => Some(TextEdit::Move{motion: CaretMotion::End, select: select}),
+        Key::Character('a') if modifiers.ctrl => Some(TextEdit::SelectAll),
+        _ => None
+    }
+}
+
+fn accepts_keyboard_focus(widget: &Widget) -> bool {
+    match *widget {
+        Widget::Button{..} => true,
+        Widget::Textbox{..} => true,
+        _ => false
+    }
+}
+
 fn inside_rect(rect: Rect, x: i32, y: i32) -> bool {
     if x >= rect.x && x < rect.x + rect.w - 1 &&
        y >= rect.y && y < rect.y + rect.h - 1 {
@@ -196,16 +472,38 @@ fn inside_rect(rect: Rect, x: i32, y: i32) -> bool {
     }
 }
 
+//Places a widget rect inside its parent's screen rect and clips it to the parent
+fn absolute_rect(widget: &Widget, parent_rect: Rect) -> Rect {
+    let rect = match get_widget_rect(widget) {
+        Some(rect) => rect,
+        None => {
+            //Widgets without a size extend to the parent's edges
+            let (ix, iy) = get_widget_position(widget);
+            Rect {x: ix, y: iy, w: parent_rect.w - ix, h: parent_rect.h - iy}
+        }
+    };
+
+    Rect {
+        x: rect.x + parent_rect.x,
+        y: rect.y + parent_rect.y,
+        w: std::cmp::max(0, std::cmp::min(rect.w, parent_rect.w - rect.x)),
+        h: std::cmp::max(0, std::cmp::min(rect.h, parent_rect.h - rect.y)),
+    }
+}
+
 /***********************************************************************************
  *      render_form
  *      render_button
+ *      render_textbox
  *      render_text
  *    
  *      Auxiliary functions to create RenderJobs
  ***********************************************************************************/
-fn render_form(index: WidgetHandle, focus: bool, rect: Rect, title: &str) -> RenderJob {
+fn render_form(index: WidgetHandle, hovered: bool, keyboard_focused: bool, disabled: bool, rect: Rect, title: &str) -> RenderJob {
     RenderJob::Form {   index: index,
-                        focus: focus,
+                        hovered: hovered,
+                        keyboard_focused: keyboard_focused,
+                        disabled: disabled,
                         x: rect.x, 
                         y: rect.y, 
                         w: rect.w, 
@@ -214,10 +512,12 @@ fn render_form(index: WidgetHandle, focus: bool, rect: Rect, title: &str) -> Ren
     }
 }
 
-fn render_button(index: WidgetHandle, pressed: bool, focus: bool, rect: Rect, text: &str) -> RenderJob {
+fn render_button(index: WidgetHandle, pressed: bool, hovered: bool, keyboard_focused: bool, disabled: bool, rect: Rect, text: &str) -> RenderJob {
     RenderJob::Button{  index: index,
                         pressed: pressed,
-                        focus: focus,
+                        hovered: hovered,
+                        keyboard_focused: keyboard_focused,
+                        disabled: disabled,
                         x: rect.x, 
                         y: rect.y, 
                         w: rect.w, 
@@ -225,480 +525,1834 @@ fn render_button(index: WidgetHandle, pressed: bool, focus: bool, rect: Rect, te
                         text: text.to_owned() }
 }
 
-pub fn render_text(index: WidgetHandle, x: i32, y: i32, text: &str) -> RenderJob {
+fn render_textbox(index: WidgetHandle, hovered: bool, keyboard_focused: bool, disabled: bool, rect: Rect, text: &str, caret: usize, selection: Option<usize>) -> RenderJob {
+    RenderJob::Textbox{ index: index,
+                        hovered: hovered,
+                        keyboard_focused: keyboard_focused,
+                        disabled: disabled,
+                        x: rect.x, 
+                        y: rect.y, 
+                        w: rect.w, 
+                        h: rect.h,
+                        text: text.to_owned(),
+                        caret: caret,
+                        selection: selection_range(caret, selection) }
+}
+
+impl RenderJob {
+    pub fn handle(&self) -> Option<WidgetHandle> {
+        match *self {
+            RenderJob::Nul => None,
+            RenderJob::Form{index, ..} => Some(index),
+            RenderJob::Button{index, ..} => Some(index),
+            RenderJob::Label{index, ..} => Some(index),
+            RenderJob::Textbox{index, ..} => Some(index),
+        }
+    }
+}
+
+pub fn render_text(index: WidgetHandle, disabled: bool, x: i32, y: i32, text: &str) -> RenderJob {
     RenderJob::Label {
         index: index,
+        disabled: disabled,
         text: text.to_owned(), 
         x: x, 
         y: y
     }
 }
 
-pub type WidgetHandle = i32;
+/***********************************************************************************
+ *      WidgetId
+ *      
+ *      Opaque handle to a widget. The slot of a removed widget can be reused
+ *      by a new widget, but the generation counter makes sure that handles
+ *      to the removed widget are detected as stale instead of pointing at
+ *      the new one.
+ ***********************************************************************************/
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct WidgetId {
+    index: u32,
+    generation: u32,
+}
+
+impl fmt::Display for WidgetId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}v{}", self.index, self.generation)
+    }
+}
+
+#[cfg(test)]
+impl WidgetId {
+    //Handles for tests that don't need a UI, like the layout tests
+    pub fn from_index(index: u32) -> WidgetId {
+        WidgetId {index: index, generation: 0}
+    }
+}
+
+pub type WidgetHandle = WidgetId;
+
+//Size of the text in labels, buttons and text boxes
+pub const FONT_SIZE: u32 = 18;
+
+//Frame around the children of a Form, the title bar includes the top border
+pub const FORM_BORDER: i32 = 3;
+pub const TITLE_BAR_HEIGHT: i32 = 24;
 pub type Depth = u32;
 
-pub struct UI {
-    widgets: Vec<(Depth, Widget)>,
-    widget_indices: Vec<usize>,
-    mouse_focused_widgets: Vec<WidgetHandle>,
-    events: Vec<WidgetEvent>,
-    screen_rect: Rect,
-    dragged_window: WidgetHandle,
-    pub fonts: conrod::text::font::Map,
-    pub default_font: Option<conrod::text::font::Id>,
+#[derive(Debug, Clone, PartialEq)]
+pub enum UiError {
+    StaleHandle(WidgetHandle),
+    NotAChild { parent: WidgetHandle, child: WidgetHandle },
+    CyclicParent { parent: WidgetHandle, child: WidgetHandle },
+    TooManyWidgets,
+    Layout(layout::LayoutError),
 }
 
-impl UI {
-    pub fn new (screen_width: i32, screen_height: i32) -> UI {
-        
-        UI {
-            widgets: Vec::new(),
-            widget_indices: Vec::new(),
-            mouse_focused_widgets: Vec::new(),
-            events: Vec::new(),
-            screen_rect: Rect {x: 0, y: 0, w: screen_width, h: screen_height},
-            dragged_window: -1,
-            fonts: conrod::text::font::Map::new(),
-            default_font: None,
+impl fmt::Display for UiError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            UiError::StaleHandle(handle) => write!(f, "Widget {} has been removed", handle),
+            UiError::NotAChild{parent, child} => write!(f, "Widget {} is not a child of widget {}", child, parent),
+            UiError::CyclicParent{parent, child} => write!(f, "Widget {} can't be moved into its own subtree at widget {}", child, parent),
+            UiError::TooManyWidgets => write!(f, "Too many widgets"),
+            UiError::Layout(ref e) => write!(f, "{}", e),
         }
     }
-    pub fn clear_events(&mut self) {
-        self.events.clear();
+}
+
+impl Error for UiError {}
+
+impl From<layout::LayoutError> for UiError {
+    fn from(e: layout::LayoutError) -> UiError {
+        UiError::Layout(e)
     }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Parent {
+    Root,
+    Widget(WidgetHandle)
+}
 
-    fn find_widget_index_by_handle(&self, handle: WidgetHandle) -> usize {
-        debug_assert!(handle >= 0);
-        return self.widget_indices[handle as usize];
+impl From<WidgetHandle> for Parent {
+    fn from(handle: WidgetHandle) -> Parent {
+        Parent::Widget(handle)
     }
+}
 
-    fn find_widget_handle_by_index(&self, index: usize) -> WidgetHandle {
-        for (handle, i) in self.widget_indices.iter().enumerate() {
-            if *i == index {return handle as WidgetHandle;}
-        }
+struct Node {
+    widget: Widget,
+    depth: Depth,
+    //Creation order, handles are reused and say nothing about the order
+    serial: u64,
+    parent: Option<u32>,
+    first_child: Option<u32>,
+    last_child: Option<u32>,
+    prev_sibling: Option<u32>,
+    next_sibling: Option<u32>,
+    layout: Option<layout::ChildLayout>,
+    layout_dirty: bool,
+    visible: bool,
+    enabled: bool,
+    name: Option<String>,
+}
+
+struct Slot {
+    generation: u32,
+    node: Option<Node>,
+}
 
-        panic!("Widget handle not found");
+//Walks the widget tree depth first, parents before their children and
+//siblings from bottom to top, which is the order widgets are drawn in
+struct Preorder<'a> {
+    ui: &'a UI,
+    next: Option<u32>,
+    root: Option<u32>,
+}
+
+impl<'a> Iterator for Preorder<'a> {
+    type Item = u32;
+
+    fn next(&mut self) -> Option<u32> {
+        let slot = match self.next {
+            Some(slot) => slot,
+            None => return None
+        };
+
+        self.next = match self.ui.node(slot).first_child {
+            Some(child) => Some(child),
+            None => {
+                //Climb up until a widget with a sibling above it is found,
+                //but never past the root of the walked subtree
+                let mut current = slot;
+                loop {
+                    if Some(current) == self.root {break None;}
+
+                    let node = self.ui.node(current);
+                    if let Some(sibling) = node.next_sibling {break Some(sibling);}
+
+                    match node.parent {
+                        Some(parent) => current = parent,
+                        None => break None
+                    }
+                }
+            }
+        };
+
+        Some(slot)
     }
+}
 
 /***********************************************************************************
- *      UI::add_widget
+ *      Children
+ *      Descendants
+ *      Ancestors
  *      
- *      Adds a widget to the UI with the specified parent. If -1 is used as
- *      parent the widget will be a root window.
- *      Returns a handle to the created widget for manipulation.
+ *      Iterators over the widget tree, created by UI::children,
+ *      UI::descendants and UI::ancestors.
  ***********************************************************************************/
-    pub fn add_widget(&mut self, parent: WidgetHandle, w: Widget) -> WidgetHandle {
-        let handle = self.widget_indices.len();
+pub struct Children<'a> {
+    ui: &'a UI,
+    next: Option<u32>,
+}
 
-        if handle >= std::i32::MAX as usize {
-            panic!("Too many widgets");
-        }
+impl<'a> Iterator for Children<'a> {
+    type Item = WidgetHandle;
 
-        if parent != -1 {
-            //We want to put the widget after the parent
-            //in the vector to always keep the vector sorted
-            let parent_index = self.find_widget_index_by_handle(parent);
-            
-            //Fix the widgets index map because of insertion
-            //in the middle of the vector
-            for i in parent_index + 1 .. self.widgets.len() {
-                let tmp = self.find_widget_handle_by_index(i) as usize;
-                self.widget_indices[tmp] = self.widget_indices[tmp] + 1;
+    fn next(&mut self) -> Option<WidgetHandle> {
+        match self.next {
+            Some(slot) => {
+                self.next = self.ui.node(slot).next_sibling;
+                Some(self.ui.handle_of(slot))
             }
+            None => None
+        }
+    }
+}
 
-            let parent_depth = self.widgets[parent_index].0;
+pub struct Descendants<'a> {
+    walk: Preorder<'a>,
+}
 
-            self.widget_indices.push(parent_index + 1);
-            self.widgets.insert(parent_index + 1, (parent_depth + 1, w)); //depth + 1 since this is a child window
+impl<'a> Iterator for Descendants<'a> {
+    type Item = WidgetHandle;
 
-        } else {
-            //This is a root window - just append it last to the vector
-            let index = self.widgets.len();
-            self.widget_indices.push(index);
-            self.widgets.push((0, w)); //Root windows have depth 0
+    fn next(&mut self) -> Option<WidgetHandle> {
+        match self.walk.next() {
+            Some(slot) => Some(self.walk.ui.handle_of(slot)),
+            None => None
         }
-
-        handle as WidgetHandle
     }
+}
 
+pub struct Ancestors<'a> {
+    ui: &'a UI,
+    next: Option<u32>,
+}
 
-    fn size_hint(&self, widget: &Widget) ->(u32, u32) {
-        match *widget {
-            Widget::Empty => (0, 0),
-            Widget::Form {..} => (300, 300),
-            Widget::Label{ref text, ..} => (conrod::text::line::width(text, self.fonts.get(self.default_font.unwrap()).unwrap(), 18) as u32, 
-                                            conrod::text::height(1, 18, 0.0) as u32),
-            Widget::Button{ref text, ..} => {
-                let mut width = conrod::text::line::width(text, self.fonts.get(self.default_font.unwrap()).unwrap(), 18) as u32 + 20;
-                let height = conrod::text::height(1, 18, 0.0) as u32 + 20;
-                if width < 100 {width = 100}
-                (width, height)
-            },
-            Widget::Textbox{..} => (250, 30),
+impl<'a> Iterator for Ancestors<'a> {
+    type Item = WidgetHandle;
+
+    fn next(&mut self) -> Option<WidgetHandle> {
+        match self.next {
+            Some(slot) => {
+                self.next = self.ui.node(slot).parent;
+                Some(self.ui.handle_of(slot))
+            }
+            None => None
         }
     }
+}
 
-    pub fn set_layout(&mut self, parent: WidgetHandle, access_closure: &Fn(&mut layout::AccessLayout)) {
-        let mut layout = layout::GridLayout::new();
+pub struct UI {
+    slots: Vec<Slot>,
+    free_slots: Vec<u32>,
+    first_root: Option<u32>,
+    last_root: Option<u32>,
+    widget_count: usize,
+    next_serial: u64,
+    dirty_layouts: Vec<WidgetHandle>,
+    mouse_focused_widgets: Vec<WidgetHandle>,
+    events: VecDeque<WidgetEvent>,
+    screen_rect: Rect,
+    dragged_window: Option<WidgetHandle>,
+    keyboard_focus: Option<WidgetHandle>,
+    callbacks: HashMap<(WidgetHandle, CallbackKind), Vec<Callback>>,
+    dispatched_events: usize,
+    text_measurer: Option<Box<TextMeasurer>>,
+}
 
-        layout.access(&access_closure, &|l| -> (u32, u32) {
-            let index = self.find_widget_index_by_handle(l);
-            self.size_hint(&self.widgets[index].1)
-        });
+impl UI {
+    pub fn new (screen_width: i32, screen_height: i32) -> UI {
+        
+        UI {
+            slots: Vec::new(),
+            free_slots: Vec::new(),
+            first_root: None,
+            last_root: None,
+            widget_count: 0,
+            next_serial: 0,
+            dirty_layouts: Vec::new(),
+            mouse_focused_widgets: Vec::new(),
+            events: VecDeque::new(),
+            screen_rect: Rect {x: 0, y: 0, w: screen_width, h: screen_height},
+            dragged_window: None,
+            keyboard_focus: None,
+            callbacks: HashMap::new(),
+            dispatched_events: 0,
+            text_measurer: None,
+        }
+    }
+    pub fn clear_events(&mut self) {
+        self.events.clear();
+        self.dispatched_events = 0;
+    }
 
-        layout.update(&mut |item, rect: (u32, u32, u32, u32)| {
-            let index = self.find_widget_index_by_handle(item);
-            
-            set_widget_position(&mut self.widgets[index].1, rect.0 as i32, rect.1 as i32);
-            set_widget_size(&mut self.widgets[index].1, rect.2 as i32, rect.3 as i32 );
-        });
+/***********************************************************************************
+ *      UI::poll_event
+ *      UI::drain_events
+ *      
+ *      Reads the events pushed by the widgets in the order they happened.
+ *      Read events are removed from the queue.
+ ***********************************************************************************/
+    pub fn poll_event(&mut self) -> Option<WidgetEvent> {
+        if self.dispatched_events > 0 { self.dispatched_events = self.dispatched_events - 1; }
+        self.events.pop_front()
+    }
 
+    pub fn drain_events(&mut self) -> std::collections::vec_deque::Drain<WidgetEvent> {
+        self.dispatched_events = 0;
+        self.events.drain(..)
     }
 
 /***********************************************************************************
- *      UI::mousemove
+ *      UI::on_click
+ *      UI::on_change
+ *      UI::on_close
  *      
- *      Handles a mouse move event
- *      Drags widgets and handles widgets losing mouse focus
+ *      Registers a closure that is called when a button is clicked, when the
+ *      text of a text box changes or when a form is asked to close.
+ *      Callbacks run after the input entry points have processed the input
+ *      and get mutable access to the UI. The events also stay in the queue
+ *      for poll_event and drain_events.
  ***********************************************************************************/
-    pub fn mousemove(&mut self, last_mx: i32, last_my: i32, mx: i32, my: i32) {
-        if self.dragged_window >= 0 {
-            let index = self.find_widget_index_by_handle(self.dragged_window);
-            match self.widgets[index].1 {
-                Widget::Form{ref mut position, ..} => {
-                    position.x+= mx - last_mx;
-                    position.y+= my - last_my;
-                }
-                _ => ()
-            }
+    pub fn on_click<F>(&mut self, handle: WidgetHandle, callback: F) where F: FnMut(&mut UI, &WidgetEvent) + 'static {
+        self.add_callback(handle, CallbackKind::Click, Box::new(callback));
+    }
 
-            return ;
-        }
+    pub fn on_change<F>(&mut self, handle: WidgetHandle, callback: F) where F: FnMut(&mut UI, &WidgetEvent) + 'static {
+        self.add_callback(handle, CallbackKind::Change, Box::new(callback));
+    }
 
+    pub fn on_close<F>(&mut self, handle: WidgetHandle, callback: F) where F: FnMut(&mut UI, &WidgetEvent) + 'static {
+        self.add_callback(handle, CallbackKind::Close, Box::new(callback));
+    }
 
-        #[derive(PartialEq)]
-        enum Iteration {
-            Child,
-            Sibling
-        }
+    fn add_callback(&mut self, handle: WidgetHandle, kind: CallbackKind, callback: Callback) {
+        self.callbacks.entry((handle, kind)).or_insert_with(Vec::new).push(callback);
+    }
 
-        let mut focused_widgets: Vec<i32> = Vec::new();
-        let mut next_iteration = Iteration::Child;
+    fn dispatch_callbacks(&mut self) {
+        //Callbacks may push new events, those are dispatched in the same loop
+        while self.dispatched_events < self.events.len() {
+            let event = self.events[self.dispatched_events].clone();
+            self.dispatched_events = self.dispatched_events + 1;
 
-        let mut last_rect = self.screen_rect.clone();
-        let mut last_depth = 0;
+            let key = match callback_target(&event) {
+                Some(key) => key,
+                None => continue
+            };
 
-        let mut rects: Vec<(Depth, Rect)>;
-        rects = Vec::new();
-        rects.push( (last_depth, last_rect) );
+            //The callbacks are taken out of the map while they run so they can borrow the UI
+            if let Some(mut callbacks) = self.callbacks.remove(&key) {
+                for callback in callbacks.iter_mut() {
+                    callback(self, &event);
+                }
 
-        let mut index = 0; 
-        'loop_widgets: for widget in &self.widgets {
-            //If the new widget is a child widget of the last widget
-            if last_depth < widget.0  {
-                if next_iteration == Iteration::Sibling { //Don't compare to child widgets
-                    index+= 1;
-                    continue 'loop_widgets;               //If the mouse cursor wasn't within parent
+                if let Some(added) = self.callbacks.remove(&key) {
+                    callbacks.extend(added);
                 }
 
-                rects.push( (last_depth, last_rect) );
-                next_iteration == Iteration::Sibling;
-            }
-            //If the new widget is higher in the tree
-            else if last_depth > widget.0  {
-                while last_depth != widget.0  { //pop the stack until we're at this widgets depth
-                    rects.pop();
-                    let (ld, lr) = rects.last().unwrap().clone();
-                    last_depth = ld;
-                    last_rect = lr;
+                //A callback may have removed its own widget
+                if self.contains(key.0) {
+                    self.callbacks.insert(key, callbacks);
                 }
             }
-            //The widget is a sibling of the previous one
-            else {
-                let (_, lr) = rects.last().unwrap().clone();
-                last_rect = lr;
-            }
+        }
+    }
 
-            let rect = get_widget_rect(&widget.1);
-            
-            let final_rect = match rect {
-                Some(rect) => {
-                    let parent_rect = last_rect;
-                    
-                    let final_x = rect.x + parent_rect.x;
-                    let final_y = rect.y + parent_rect.y;
-                    let mut final_w = rect.w;
-                    let mut final_h = rect.h;
+/***********************************************************************************
+ *      UI::request_close
+ *      
+ *      Asks a form to close by pushing a CloseRequested event. Pressing
+ *      Escape inside a form does the same. The form stays open until the
+ *      application removes or hides it.
+ ***********************************************************************************/
+    pub fn request_close(&mut self, handle: WidgetHandle) {
+        self.events.push_back(WidgetEvent::CloseRequested(handle));
+        self.dispatch_callbacks();
+    }
 
-                    if rect.x + rect.w > parent_rect.w {
-                        final_w = parent_rect.w - final_x;
-                    }
+    fn find_slot(&self, handle: WidgetHandle) -> Option<u32> {
+        match self.slots.get(handle.index as usize) {
+            Some(slot) if slot.generation == handle.generation && slot.node.is_some() => Some(handle.index),
+            _ => None
+        }
+    }
 
-                    if rect.y + rect.h > parent_rect.h {
-                        final_h = parent_rect.h - final_y;
-                    }
-                    Rect {
-                        x: final_x,
-                        y: final_y,
-                        w: final_w,
-                        h: final_h
-                    }
-                }
-                None => {
-                    let parent_rect = last_rect;
-                    let (ix, iy) = get_widget_position(&widget.1);
-
-                    Rect {
-                        x: ix + parent_rect.x, 
-                        y: iy + parent_rect.y, 
-                        w: parent_rect.w - ix, 
-                        h: parent_rect.h - iy 
-                    }
-                }
-            };
+    //For handles the UI keeps itself, those are always removed together with the widget
+    fn slot_of(&self, handle: WidgetHandle) -> u32 {
+        match self.find_slot(handle) {
+            Some(slot) => slot,
+            None => panic!("Widget {} has been removed", handle)
+        }
+    }
 
-            if inside_rect(final_rect, mx, my) {
-                match widget.1 {
-                    Widget::Label{..} => (), //Labels do never get mouse focus
-                    _ =>    {
-                        //If a sibling widget below this one has been tagged as focused widget it and
-                        //it's child widgets must be removed from the focused widgets list
-                        focused_widgets.retain(|&x| widget.0 > self.widgets[self.find_widget_index_by_handle(x)].0);
+    fn handle_of(&self, slot: u32) -> WidgetHandle {
+        WidgetId {index: slot, generation: self.slots[slot as usize].generation}
+    }
 
-                        focused_widgets.push(self.find_widget_handle_by_index(index));
-                        next_iteration = Iteration::Child;
-                    }
-                } 
-            }
-            else {
-                next_iteration = Iteration::Sibling;
-            }
+    fn node(&self, slot: u32) -> &Node {
+        match self.slots[slot as usize].node {
+            Some(ref node) => node,
+            None => panic!("Widget slot {} is linked but empty", slot)
+        }
+    }
 
-            last_rect = final_rect;
-            last_depth = widget.0;
-            index+= 1;
+    fn node_mut(&mut self, slot: u32) -> &mut Node {
+        match self.slots[slot as usize].node {
+            Some(ref mut node) => node,
+            None => panic!("Widget slot {} is linked but empty", slot)
         }
+    }
 
-        //Check if any widgets lost focus
-        {
-            let mut iter = focused_widgets.iter();
-            for w in &self.mouse_focused_widgets {
-                if iter.any(|x| *x == *w) { }
-                else {
-                    let i = self.find_widget_index_by_handle(*w);
-                    match self.widgets[i].1 {
-                        Widget::Button{ref mut pressed, ..} => *pressed = false,
-                        _ => ()
-                    }
-                }
-            }
+    fn subtree(&self, slot: u32) -> Preorder {
+        Preorder {ui: self, next: Some(slot), root: Some(slot)}
+    }
+
+    fn find_root(&self, slot: u32) -> u32 {
+        let mut root = slot;
+        while let Some(parent) = self.node(root).parent {
+            root = parent;
         }
+        root
+    }
 
+    fn is_within(&self, slot: u32, ancestor: u32) -> bool {
+        let mut current = Some(slot);
+        while let Some(s) = current {
+            if s == ancestor {return true;}
+            current = self.node(s).parent;
+        }
+        false
+    }
 
-        self.mouse_focused_widgets = focused_widgets;
+    fn has_keyboard_focus_within(&self, slot: u32) -> bool {
+        match self.keyboard_focus {
+            Some(handle) => self.is_within(self.slot_of(handle), slot),
+            None => false
+        }
     }
 
-    pub fn move_widget_to_front(&mut self, index: usize) {
-        let s_len = self.widgets.len();
-        let mut last_child = s_len - 1;
-        let destination_depth = self.widgets[index].0;
+    //A widget is only shown if all of its parents are shown
+    fn is_shown(&self, slot: u32) -> bool {
+        let mut current = Some(slot);
+        while let Some(s) = current {
+            let node = self.node(s);
+            if !node.visible {return false;}
+            current = node.parent;
+        }
+        true
+    }
 
-        //Count number of children in this widget
-        for (iterations, w) in self.widgets[index  + 1 .. s_len].iter().enumerate() {
-            if w.0 <= destination_depth {
-                last_child = index + iterations;
-                break;
-            }
+    //A widget only takes input if it and all of its parents are shown and enabled
+    fn is_usable(&self, slot: u32) -> bool {
+        let mut current = Some(slot);
+        while let Some(s) = current {
+            let node = self.node(s);
+            if !node.visible || !node.enabled {return false;}
+            current = node.parent;
         }
-        
-        //Move widget including children to temporary vector
-        let mut temporary_container: Vec<(Depth, Widget)> = Vec::new();
-        let mut temporary_indices: Vec<WidgetHandle> = Vec::new();
-        temporary_container.resize(last_child - index + 1, (0, Widget::Empty));
-        temporary_indices.reserve(last_child - index + 1);
-        temporary_container.clone_from_slice(&self.widgets[index .. last_child + 1 ]);
-        for i in index .. last_child + 1 { temporary_indices.push(self.find_widget_handle_by_index(i)); }
+        true
+    }
 
-        //Count number of siblings including their children
-        let first_sibling = last_child + 1;
-        let mut num_siblings = s_len - first_sibling;
-        for (iterations, w) in self.widgets[first_sibling .. s_len].iter().enumerate() {
-            if w.0 < destination_depth {
-                num_siblings = iterations;
-                break;
-            }
+    //Drops the keyboard focus, drag and hover state held inside a subtree
+    //that can no longer take input
+    fn release_input_within(&mut self, slot: u32) {
+        if self.has_keyboard_focus_within(slot) {
+            self.set_keyboard_focus(None);
         }
 
-        //Move sibling widgets up
-        for i in 0 .. num_siblings {
-            self.widgets.swap(index + i, first_sibling + i);
-            let handle = self.find_widget_handle_by_index(first_sibling + i) as usize;
-            self.widget_indices[handle] = index + i;
+        if let Some(dragged) = self.dragged_window {
+            if self.is_within(self.slot_of(dragged), slot) { self.dragged_window = None; }
         }
 
-        //Return widgets to the widgets array
-        let len = temporary_container.len();
-        for i in 0 .. len {
-            std::mem::swap(&mut temporary_container[i], &mut self.widgets[index + num_siblings + i] );
-        } 
+        let released: Vec<WidgetHandle> = self.mouse_focused_widgets.iter()
+            .filter(|h| self.is_within(self.slot_of(**h), slot))
+            .cloned()
+            .collect();
 
-        for (i, j) in temporary_indices.iter().enumerate() {
-            self.widget_indices[*j as usize] = index + num_siblings + i;
+        for h in &released {
+            let s = self.slot_of(*h);
+            match self.node_mut(s).widget {
+                Widget::Button{ref mut pressed, ..} => *pressed = false,
+                _ => ()
+            }
         }
+        self.mouse_focused_widgets.retain(|h| !released.contains(h));
     }
 
-/***********************************************************************************
- *      UI::mousedown
- *      
+    fn set_first_child(&mut self, parent: Option<u32>, child: Option<u32>) {
+        match parent {
+            Some(parent) => self.node_mut(parent).first_child = child,
+            None => self.first_root = child
+        }
+    }
+
+    fn set_last_child(&mut self, parent: Option<u32>, child: Option<u32>) {
+        match parent {
+            Some(parent) => self.node_mut(parent).last_child = child,
+            None => self.last_root = child
+        }
+    }
+
+    //Puts the widget last among its siblings, which is on top of them
+    fn link_last(&mut self, slot: u32, parent: Option<u32>) {
+        let last = match parent {
+            Some(parent) => self.node(parent).last_child,
+            None => self.last_root
+        };
+
+        {
+            let node = self.node_mut(slot);
+            node.parent = parent;
+            node.prev_sibling = last;
+            node.next_sibling = None;
+        }
+
+        match last {
+            Some(last) => self.node_mut(last).next_sibling = Some(slot),
+            None => self.set_first_child(parent, Some(slot))
+        }
+        self.set_last_child(parent, Some(slot));
+    }
+
+    //Puts the widget below the sibling at index, or on top if there are fewer siblings
+    fn link_at(&mut self, slot: u32, parent: Option<u32>, index: usize) {
+        let mut next = match parent {
+            Some(parent) => self.node(parent).first_child,
+            None => self.first_root
+        };
+        for _ in 0 .. index {
+            match next {
+                Some(sibling) => next = self.node(sibling).next_sibling,
+                None => break
+            }
+        }
+
+        let next = match next {
+            Some(next) => next,
+            None => return self.link_last(slot, parent)
+        };
+
+        let prev = self.node(next).prev_sibling;
+        {
+            let node = self.node_mut(slot);
+            node.parent = parent;
+            node.prev_sibling = prev;
+            node.next_sibling = Some(next);
+        }
+
+        self.node_mut(next).prev_sibling = Some(slot);
+        match prev {
+            Some(prev) => self.node_mut(prev).next_sibling = Some(slot),
+            None => self.set_first_child(parent, Some(slot))
+        }
+    }
+
+    fn unlink(&mut self, slot: u32) {
+        let (parent, prev, next) = {
+            let node = self.node(slot);
+            (node.parent, node.prev_sibling, node.next_sibling)
+        };
+
+        match prev {
+            Some(prev) => self.node_mut(prev).next_sibling = next,
+            None => self.set_first_child(parent, next)
+        }
+        match next {
+            Some(next) => self.node_mut(next).prev_sibling = prev,
+            None => self.set_last_child(parent, prev)
+        }
+
+        let node = self.node_mut(slot);
+        node.prev_sibling = None;
+        node.next_sibling = None;
+    }
+
+/***********************************************************************************
+ *      UI::keyboard_focus
+ *      UI::set_keyboard_focus
+ *      
+ *      The widget that receives key and text input. Only buttons and text
+ *      boxes accept keyboard focus, and only while they are visible and
+ *      enabled. Changing the focus pushes FocusLost and FocusGained events.
+ *      Returns false if the widget can't take keyboard focus or was removed.
+ ***********************************************************************************/
+    pub fn keyboard_focus(&self) -> Option<WidgetHandle> {
+        self.keyboard_focus
+    }
+
+    pub fn set_keyboard_focus(&mut self, handle: Option<WidgetHandle>) -> bool {
+        if let Some(h) = handle {
+            let slot = match self.find_slot(h) {
+                Some(slot) => slot,
+                None => return false
+            };
+            if !accepts_keyboard_focus(&self.node(slot).widget) || !self.is_usable(slot) {return false;}
+        }
+
+        if handle == self.keyboard_focus {return true;}
+
+        if let Some(old) = self.keyboard_focus {
+            self.events.push_back(WidgetEvent::FocusLost(old));
+        }
+        if let Some(new) = handle {
+            self.events.push_back(WidgetEvent::FocusGained(new));
+        }
+        self.keyboard_focus = handle;
+        true
+    }
+
+/***********************************************************************************
+ *      UI::focus_next
+ *      UI::focus_previous
+ *      
+ *      Moves keyboard focus through the focusable widgets of the root window
+ *      that holds the focus, or the topmost root window if nothing has focus.
+ *      Children are visited in the cell order of their parent's layout,
+ *      children the layout doesn't place follow in stacking order. The
+ *      traversal wraps around.
+ ***********************************************************************************/
+    pub fn focus_next(&mut self) {
+        self.move_keyboard_focus(true);
+    }
+
+    pub fn focus_previous(&mut self) {
+        self.move_keyboard_focus(false);
+    }
+
+    fn focus_chain(&self) -> Vec<WidgetHandle> {
+        let root = match self.keyboard_focus {
+            Some(handle) => self.find_root(self.slot_of(handle)),
+            None => {
+                //The topmost root window that is shown
+                let mut root = self.last_root;
+                while let Some(slot) = root {
+                    if self.node(slot).visible {break;}
+                    root = self.node(slot).prev_sibling;
+                }
+
+                match root {
+                    Some(slot) => slot,
+                    None => return Vec::new()
+                }
+            }
+        };
+
+        let mut chain = Vec::new();
+        self.collect_focus_chain(root, &mut chain);
+        chain
+    }
+
+    fn collect_focus_chain(&self, slot: u32, chain: &mut Vec<WidgetHandle>) {
+        let node = self.node(slot);
+        if !node.visible {return;}
+        if accepts_keyboard_focus(&node.widget) && self.is_usable(slot) {
+            chain.push(self.handle_of(slot));
+        }
+
+        //The layout may still hold widgets that were removed or moved away
+        let mut children: Vec<u32> = Vec::new();
+        if let Some(ref layout) = node.layout {
+            layout.update(&mut |item, _| match self.find_slot(item) {
+                Some(child) if self.node(child).parent == Some(slot) => children.push(child),
+                _ => ()
+            });
+        }
+        let placed: HashSet<u32> = children.iter().cloned().collect();
+
+        let mut child = node.first_child;
+        while let Some(c) = child {
+            if !placed.contains(&c) {children.push(c);}
+            child = self.node(c).next_sibling;
+        }
+
+        for child in children {
+            self.collect_focus_chain(child, chain);
+        }
+    }
+
+    fn move_keyboard_focus(&mut self, forward: bool) {
+        let chain = self.focus_chain();
+        if chain.is_empty() {return;}
+
+        let current = match self.keyboard_focus {
+            Some(handle) => chain.iter().position(|h| *h == handle),
+            None => None
+        };
+
+        let next = match (current, forward) {
+            (Some(i), true) => (i + 1) % chain.len(),
+            (Some(i), false) => (i + chain.len() - 1) % chain.len(),
+            (None, true) => 0,
+            (None, false) => chain.len() - 1,
+        };
+
+        self.set_keyboard_focus(Some(chain[next]));
+    }
+
+/***********************************************************************************
+ *      UI::add_widget
+ *      
+ *      Adds a widget to the UI with the specified parent. If Parent::Root is
+ *      used as parent the widget will be a root window. The widget is put on
+ *      top of the widgets that already share its parent. A parent with a
+ *      box layout places the widget after its other children.
+ *      Returns a handle to the created widget for manipulation.
+ ***********************************************************************************/
+    pub fn add_widget<P: Into<Parent>>(&mut self, parent: P, w: Widget) -> Result<WidgetHandle, UiError> {
+        if self.free_slots.is_empty() && self.slots.len() >= std::u32::MAX as usize {
+            return Err(UiError::TooManyWidgets);
+        }
+
+        let (parent, depth) = match parent.into() {
+            Parent::Widget(parent) => match self.find_slot(parent) {
+                Some(slot) => (Some(slot), self.node(slot).depth + 1), //depth + 1 since this is a child window
+                None => return Err(UiError::StaleHandle(parent))
+            },
+            Parent::Root => (None, 0) //Root windows have depth 0
+        };
+
+        let node = Node {
+            widget: w,
+            depth: depth,
+            serial: self.next_serial,
+            parent: None,
+            first_child: None,
+            last_child: None,
+            prev_sibling: None,
+            next_sibling: None,
+            layout: None,
+            layout_dirty: false,
+            visible: true,
+            enabled: true,
+            name: None,
+        };
+        self.next_serial = self.next_serial + 1;
+
+        let handle = self.allocate_handle(node);
+        self.link_last(handle.index, parent);
+        self.widget_count = self.widget_count + 1;
+        self.flow_into_layout(handle.index);
+        Ok(handle)
+    }
+
+    fn allocate_handle(&mut self, node: Node) -> WidgetHandle {
+        match self.free_slots.pop() {
+            Some(i) => {
+                let slot = &mut self.slots[i as usize];
+                slot.generation = slot.generation + 1;
+                slot.node = Some(node);
+                WidgetId {index: i, generation: slot.generation}
+            }
+            None => {
+                self.slots.push(Slot {generation: 0, node: Some(node)});
+                WidgetId {index: (self.slots.len() - 1) as u32, generation: 0}
+            }
+        }
+    }
+
+/***********************************************************************************
+ *      UI::reparent
+ *      
+ *      Moves a widget together with its children to a new parent, or makes
+ *      it a root window with Parent::Root. The widget keeps its position
+ *      relative to the parent. index is its place among the new siblings,
+ *      0 is at the bottom and indices past the last sibling put it on top.
+ *      A layout of the old parent lets go of the widget, a box layout of
+ *      the new parent places it last. Under a hidden or disabled parent the
+ *      widgets lose keyboard focus, hover and drag.
+ ***********************************************************************************/
+    pub fn reparent<P: Into<Parent>>(&mut self, handle: WidgetHandle, new_parent: P, index: usize) -> Result<(), UiError> {
+        let slot = match self.find_slot(handle) {
+            Some(slot) => slot,
+            None => return Err(UiError::StaleHandle(handle))
+        };
+
+        let (parent, depth) = match new_parent.into() {
+            Parent::Widget(parent) => {
+                let parent_slot = match self.find_slot(parent) {
+                    Some(parent_slot) => parent_slot,
+                    None => return Err(UiError::StaleHandle(parent))
+                };
+                if self.is_within(parent_slot, slot) {
+                    return Err(UiError::CyclicParent{parent: parent, child: handle});
+                }
+                (Some(parent_slot), self.node(parent_slot).depth + 1)
+            }
+            Parent::Root => (None, 0)
+        };
+
+        self.mark_layout_dirty(slot);
+        self.unlink(slot);
+        self.link_at(slot, parent, index);
+        self.flow_into_layout(slot);
+
+        let old_depth = self.node(slot).depth;
+        if old_depth != depth {
+            let subtree: Vec<u32> = self.subtree(slot).collect();
+            for s in subtree {
+                let node = self.node_mut(s);
+                node.depth = node.depth - old_depth + depth;
+            }
+        }
+
+        if !self.is_usable(slot) {
+            self.release_input_within(slot);
+        }
+
+        Ok(())
+    }
+
+/***********************************************************************************
+ *      UI::remove_widget
+ *      
+ *      Removes a widget together with all of its child widgets. Handles to
+ *      the removed widgets become stale. Their slots are reused with a new
+ *      generation, so a stale handle never refers to another widget.
+ *      Returns false if the widget was already removed.
+ ***********************************************************************************/
+    pub fn remove_widget(&mut self, handle: WidgetHandle) -> bool {
+        let root = match self.find_slot(handle) {
+            Some(slot) => slot,
+            None => return false
+        };
+
+        let removed: HashSet<WidgetHandle> = self.subtree(root).map(|slot| self.handle_of(slot)).collect();
+        self.mark_layout_dirty(root);
+        self.unlink(root);
+
+        for h in &removed {
+            let slot = &mut self.slots[h.index as usize];
+            slot.node = None;
+
+            //A slot whose generation counter ran out is retired for good
+            if slot.generation < std::u32::MAX {
+                self.free_slots.push(h.index);
+            }
+        }
+        self.widget_count = self.widget_count - removed.len();
+
+        self.mouse_focused_widgets.retain(|h| !removed.contains(h));
+        self.callbacks.retain(|key, _| !removed.contains(&key.0));
+
+        if let Some(dragged) = self.dragged_window {
+            if removed.contains(&dragged) { self.dragged_window = None; }
+        }
+
+        if let Some(focus) = self.keyboard_focus {
+            if removed.contains(&focus) { self.keyboard_focus = None; }
+        }
+
+        true
+    }
+
+/***********************************************************************************
+ *      UI::contains
+ *      
+ *      Checks if a handle refers to a widget that hasn't been removed.
+ ***********************************************************************************/
+    pub fn contains(&self, handle: WidgetHandle) -> bool {
+        self.find_slot(handle).is_some()
+    }
+
+/***********************************************************************************
+ *      UI::widget
+ *      
+ *      Looks up a widget by handle. Returns None for stale handles.
+ ***********************************************************************************/
+    pub fn widget(&self, handle: WidgetHandle) -> Option<&Widget> {
+        match self.find_slot(handle) {
+            Some(slot) => Some(&self.node(slot).widget),
+            None => None
+        }
+    }
+
+/***********************************************************************************
+ *      UI::parent
+ *      UI::depth
+ *      
+ *      The parent of a widget, None for root windows, and the number of
+ *      widgets above it in the tree. Root windows have depth 0.
+ *      Both return None for stale handles.
+ ***********************************************************************************/
+    pub fn parent(&self, handle: WidgetHandle) -> Option<WidgetHandle> {
+        match self.find_slot(handle) {
+            Some(slot) => self.node(slot).parent.map(|parent| self.handle_of(parent)),
+            None => None
+        }
+    }
+
+    pub fn depth(&self, handle: WidgetHandle) -> Option<Depth> {
+        match self.find_slot(handle) {
+            Some(slot) => Some(self.node(slot).depth),
+            None => None
+        }
+    }
+
+/***********************************************************************************
+ *      UI::children
+ *      UI::descendants
+ *      UI::ancestors
+ *      
+ *      Iterates the direct children of a widget, or the root windows for
+ *      Parent::Root, from the bottom to the top. Descendants are visited
+ *      depth first in drawing order, ancestors from the parent up to the
+ *      root window. The iterators are empty for stale handles.
+ ***********************************************************************************/
+    pub fn children<P: Into<Parent>>(&self, parent: P) -> Children {
+        let first = match parent.into() {
+            Parent::Root => self.first_root,
+            Parent::Widget(handle) => match self.find_slot(handle) {
+                Some(slot) => self.node(slot).first_child,
+                None => None
+            }
+        };
+
+        Children {ui: self, next: first}
+    }
+
+    pub fn descendants(&self, handle: WidgetHandle) -> Descendants {
+        let mut walk = match self.find_slot(handle) {
+            Some(slot) => self.subtree(slot),
+            None => Preorder {ui: self, next: None, root: None}
+        };
+        walk.next(); //Skip the widget itself
+
+        Descendants {walk: walk}
+    }
+
+    pub fn ancestors(&self, handle: WidgetHandle) -> Ancestors {
+        let first = match self.find_slot(handle) {
+            Some(slot) => self.node(slot).parent,
+            None => None
+        };
+
+        Ancestors {ui: self, next: first}
+    }
+
+/***********************************************************************************
+ *      UI::widget_at
+ *      UI::widgets_at
+ *      
+ *      Finds the topmost widget under a point in screen coordinates, and the
+ *      whole chain of widgets under it from the root window down to the
+ *      topmost one. Widgets are clipped to their parents like when they are
+ *      rendered and hidden widgets are skipped. Labels are never hit.
+ *      Doesn't change the hover state.
+ ***********************************************************************************/
+    pub fn widget_at(&self, x: i32, y: i32) -> Option<WidgetHandle> {
+        self.widgets_at(x, y).pop()
+    }
+
+    pub fn widgets_at(&self, x: i32, y: i32) -> Vec<WidgetHandle> {
+        let mut chain: Vec<(Depth, WidgetHandle)> = Vec::new();
+
+        self.walk_screen_rects(&mut |slot, rect, _| {
+            if !inside_rect(rect, x, y) {
+                return false; //Children are clipped to the parent, so they can't be hit either
+            }
+
+            let node = self.node(slot);
+            match node.widget {
+                Widget::Label{..} => (), //Labels do never get mouse focus
+                _ =>    {
+                    //A widget covers the siblings below it and their children
+                    chain.retain(|&(depth, _)| depth < node.depth);
+                    chain.push((node.depth, self.handle_of(slot)));
+                }
+            }
+            true
+        });
+
+        chain.into_iter().map(|(_, handle)| handle).collect()
+    }
+
+    //Visits the visible widgets in drawing order together with their clipped
+    //rect on the screen and whether they and all their parents are enabled.
+    //The children of a widget are skipped if visit returns false.
+    fn walk_screen_rects(&self, visit: &mut FnMut(u32, Rect, bool) -> bool) {
+        self.walk_screen_rects_from(self.first_root, self.screen_rect, true, visit);
+    }
+
+    fn walk_screen_rects_from(&self, first: Option<u32>, parent_rect: Rect, parent_enabled: bool, visit: &mut FnMut(u32, Rect, bool) -> bool) {
+        let mut sibling = first;
+        while let Some(slot) = sibling {
+            let node = self.node(slot);
+
+            //Hidden widgets are skipped together with their children
+            if node.visible {
+                let rect = absolute_rect(&node.widget, parent_rect);
+                let enabled = parent_enabled && node.enabled;
+
+                if visit(slot, rect, enabled) {
+                    self.walk_screen_rects_from(node.first_child, rect, enabled, visit);
+                }
+            }
+            sibling = node.next_sibling;
+        }
+    }
+
+/***********************************************************************************
+ *      UI::set_text_measurer
+ *      
+ *      Sets what measures the text of labels and buttons for their standard
+ *      size. Use the measurer of the backend the UI is drawn with. Layouts
+ *      are solved again with the new sizes.
+ ***********************************************************************************/
+    pub fn set_text_measurer(&mut self, measurer: Box<TextMeasurer>) {
+        self.text_measurer = Some(measurer);
+
+        //Any layout may place text
+        for (i, slot) in self.slots.iter_mut().enumerate() {
+            match slot.node {
+                Some(ref mut node) if node.layout.is_some() && !node.layout_dirty => {
+                    node.layout_dirty = true;
+                    self.dirty_layouts.push(WidgetId {index: i as u32, generation: slot.generation});
+                }
+                _ => ()
+            }
+        }
+    }
+
+    fn text_width(&self, text: &str) -> u32 {
+        match self.text_measurer {
+            Some(ref measurer) => measurer.line_width(text, FONT_SIZE).ceil() as u32,
+            None => text.chars().count() as u32 * 9 //Rough estimate until a font is loaded
+        }
+    }
+
+    fn text_height(&self) -> u32 {
+        match self.text_measurer {
+            Some(ref measurer) => measurer.line_height(FONT_SIZE).ceil() as u32,
+            None => FONT_SIZE
+        }
+    }
+
+    fn size_hint(&self, widget: &Widget) ->(u32, u32) {
+        match *widget {
+            Widget::Empty => (0, 0),
+            Widget::Form {..} => (300, 300),
+            Widget::Label{ref text, ..} => (self.text_width(text), self.text_height()),
+            Widget::Button{ref text, ..} => {
+                let mut width = self.text_width(text) + 20;
+                let height = self.text_height() + 20;
+                if width < 100 {width = 100}
+                (width, height)
+            },
+            Widget::Textbox{..} => (250, 30),
+        }
+    }
+
+/***********************************************************************************
+ *      UI::set_layout
+ *      
+ *      Places the child widgets of parent in a grid. Every widget added to
+ *      the layout must be a child of parent. The grid fills a Form below its
+ *      title bar and inside its border, and is solved again when the form
+ *      is resized. Nothing is moved if the layout can't be built.
+ ***********************************************************************************/
+    pub fn set_layout(&mut self, parent: WidgetHandle, access_closure: &Fn(&mut layout::AccessLayout)) -> Result<(), UiError> {
+        let parent_slot = match self.find_slot(parent) {
+            Some(slot) => slot,
+            None => return Err(UiError::StaleHandle(parent))
+        };
+
+        let area = layout_area(&self.node(parent_slot).widget);
+        let mut layout = layout::GridLayout::new().set_size(area.w as u32, area.h as u32);
+
+        let result = layout.access(&access_closure, &|l| self.layout_size_hint(parent_slot, l));
+
+        //Report the exact reason an item was rejected
+        if let Err(layout::LayoutError::InvalidItem(item)) = result {
+            return Err(match self.find_slot(item) {
+                Some(_) => UiError::NotAChild{parent: parent, child: item},
+                None => UiError::StaleHandle(item)
+            });
+        }
+        result?;
+
+        let layout = layout::ChildLayout::Grid(layout);
+        self.apply_layout(area, &layout);
+
+        let node = self.node_mut(parent_slot);
+        node.layout = Some(layout);
+        node.layout_dirty = false;
+        Ok(())
+    }
+
+/***********************************************************************************
+ *      UI::set_box_layout
+ *      
+ *      Places the child widgets of parent in a column with Layout::Vertical
+ *      or in a row with Layout::Horizontal, in the order they were added.
+ *      Widgets added to parent later are placed after them. Layout::NoLayout
+ *      removes the layout of parent and leaves the children where they are.
+ ***********************************************************************************/
+    pub fn set_box_layout(&mut self, parent: WidgetHandle, kind: Layout) -> Result<(), UiError> {
+        let parent_slot = match self.find_slot(parent) {
+            Some(slot) => slot,
+            None => return Err(UiError::StaleHandle(parent))
+        };
+
+        let direction = match kind {
+            Layout::NoLayout => {
+                let node = self.node_mut(parent_slot);
+                node.layout = None;
+                node.layout_dirty = false;
+                return Ok(());
+            }
+            Layout::Vertical => layout::Direction::Vertical,
+            Layout::Horizontal => layout::Direction::Horizontal
+        };
+
+        let mut box_layout = layout::BoxLayout::new(direction);
+        for child in self.children(parent) {
+            box_layout.add(child);
+        }
+
+        self.node_mut(parent_slot).layout = Some(layout::ChildLayout::Box(box_layout));
+        self.node_mut(parent_slot).layout_dirty = false;
+        self.mark_own_layout_dirty(parent_slot);
+        self.update_layouts()
+    }
+
+    pub fn layout_kind(&self, parent: WidgetHandle) -> Layout {
+        match self.find_slot(parent).and_then(|slot| self.node(slot).layout.as_ref()) {
+            Some(&layout::ChildLayout::Box(ref b)) => match b.direction() {
+                layout::Direction::Vertical => Layout::Vertical,
+                layout::Direction::Horizontal => Layout::Horizontal
+            },
+            _ => Layout::NoLayout
+        }
+    }
+
+/***********************************************************************************
+ *      UI::set_spacing
+ *      UI::set_alignment
+ *      
+ *      The distance between the children of a box layout and where they
+ *      are placed across it. Returns false if parent has no box layout.
+ ***********************************************************************************/
+    pub fn set_spacing(&mut self, parent: WidgetHandle, spacing: u32) -> bool {
+        self.change_box_layout(parent, &mut |b| {b.set_spacing(spacing); true})
+    }
+
+    pub fn set_alignment(&mut self, parent: WidgetHandle, alignment: layout::Alignment) -> bool {
+        self.change_box_layout(parent, &mut |b| {b.set_alignment(alignment); true})
+    }
+
+/***********************************************************************************
+ *      UI::set_stretch
+ *      UI::set_item_alignment
+ *      
+ *      How a widget placed in a box layout takes up left over space along
+ *      the layout and where it is placed across it. None uses the alignment
+ *      of the layout. Returns false if the widget isn't in a box layout.
+ ***********************************************************************************/
+    pub fn set_stretch(&mut self, handle: WidgetHandle, stretch: u32) -> bool {
+        match self.find_slot(handle).and_then(|slot| self.node(slot).parent) {
+            Some(parent) => {
+                let parent = self.handle_of(parent);
+                self.change_box_layout(parent, &mut |b| b.set_stretch(handle, stretch))
+            }
+            None => false
+        }
+    }
+
+    pub fn set_item_alignment(&mut self, handle: WidgetHandle, alignment: Option<layout::Alignment>) -> bool {
+        match self.find_slot(handle).and_then(|slot| self.node(slot).parent) {
+            Some(parent) => {
+                let parent = self.handle_of(parent);
+                self.change_box_layout(parent, &mut |b| b.set_item_alignment(handle, alignment))
+            }
+            None => false
+        }
+    }
+
+    //Changes the box layout of parent and solves it again when it is used next
+    fn change_box_layout(&mut self, parent: WidgetHandle, change: &mut FnMut(&mut layout::BoxLayout) -> bool) -> bool {
+        let slot = match self.find_slot(parent) {
+            Some(slot) => slot,
+            None => return false
+        };
+
+        let changed = match self.node_mut(slot).layout {
+            Some(layout::ChildLayout::Box(ref mut b)) => change(b),
+            _ => false
+        };
+        if changed { self.mark_own_layout_dirty(slot); }
+        changed
+    }
+
+    //Adds a new child to the box layout of its parent
+    fn flow_into_layout(&mut self, slot: u32) {
+        let parent = match self.node(slot).parent {
+            Some(parent) => parent,
+            None => return
+        };
+
+        let handle = self.handle_of(slot);
+        let added = match self.node_mut(parent).layout {
+            Some(layout::ChildLayout::Box(ref mut b)) => {b.add(handle); true},
+            _ => false
+        };
+        if added { self.mark_own_layout_dirty(parent); }
+    }
+
+/***********************************************************************************
+ *      UI::update_layouts
+ *      
+ *      Solves the layouts again whose widgets changed in a way that affects
+ *      their standard size, like a label getting a new text. Render and
+ *      mousemove do this on their own. A layout that can't be solved keeps
+ *      the previous widget positions.
+ ***********************************************************************************/
+    pub fn update_layouts(&mut self) -> Result<(), UiError> {
+        let mut result = Ok(());
+
+        //Solving a layout can resize forms with layouts of their own, those are
+        //solved in the same pass
+        while !self.dirty_layouts.is_empty() {
+            if let Err(e) = self.update_dirty_layouts() {
+                result = Err(e);
+            }
+        }
+
+        result
+    }
+
+    fn update_dirty_layouts(&mut self) -> Result<(), UiError> {
+        let mut result = Ok(());
+
+        for parent in std::mem::replace(&mut self.dirty_layouts, Vec::new()) {
+            let slot = match self.find_slot(parent) {
+                Some(slot) => slot,
+                None => continue
+            };
+
+            //The layout is taken out of the node while it is solved so it can borrow the UI
+            let mut layout = match self.node_mut(slot).layout.take() {
+                Some(layout) => layout,
+                None => continue
+            };
+
+            let area = layout_area(&self.node(slot).widget);
+
+            match layout.relayout((area.w as u32, area.h as u32), &|l| self.layout_size_hint(slot, l)) {
+                Ok(()) => self.apply_layout(area, &layout),
+                Err(e) => result = Err(UiError::from(e))
+            }
+
+            let node = self.node_mut(slot);
+            node.layout = Some(layout);
+            node.layout_dirty = false;
+        }
+
+        result
+    }
+
+    fn layout_size_hint(&self, parent: u32, item: WidgetHandle) -> Option<(u32, u32)> {
+        match self.find_slot(item) {
+            Some(slot) if self.node(slot).parent == Some(parent) => Some(self.size_hint(&self.node(slot).widget)),
+            _ => None
+        }
+    }
+
+    fn apply_layout(&mut self, area: Rect, layout: &layout::ChildLayout) {
+        let mut resized = Vec::new();
+
+        layout.update(&mut |item, rect: (u32, u32, u32, u32)| {
+            let slot = self.slot_of(item);
+            let widget = &mut self.node_mut(slot).widget;
+            let old_rect = get_widget_rect(widget);
+            
+            set_widget_position(widget, area.x + rect.0 as i32, area.y + rect.1 as i32);
+            set_widget_size(widget, rect.2 as i32, rect.3 as i32 );
+
+            match (old_rect, get_widget_rect(widget)) {
+                (Some(old), Some(new)) if old.w != new.w || old.h != new.h => resized.push(slot),
+                _ => ()
+            }
+        });
+
+        //Children with layouts of their own place their children again inside the new size
+        for slot in resized {
+            self.mark_own_layout_dirty(slot);
+        }
+    }
+
+    //Flags the layout the widget is placed in, if any, to be solved again
+    fn mark_layout_dirty(&mut self, slot: u32) {
+        match self.node(slot).parent {
+            Some(parent) => self.mark_own_layout_dirty(parent),
+            None => ()
+        }
+    }
+
+    //Flags the layout of the widget's children, if any, to be solved again
+    fn mark_own_layout_dirty(&mut self, slot: u32) {
+        let node = self.node_mut(slot);
+        if node.layout.is_none() || node.layout_dirty {return;}
+        node.layout_dirty = true;
+
+        let handle = self.handle_of(slot);
+        self.dirty_layouts.push(handle);
+    }
+
+/***********************************************************************************
+ *      UI::set_visible
+ *      UI::is_visible
+ *      
+ *      Hidden widgets and their children are neither rendered nor hit by the
+ *      mouse, and lose keyboard focus. is_visible is only true if the widget
+ *      and all of its parents are visible.
+ *      set_visible returns false if the widget was removed.
+ ***********************************************************************************/
+    pub fn set_visible(&mut self, handle: WidgetHandle, visible: bool) -> bool {
+        let slot = match self.find_slot(handle) {
+            Some(slot) => slot,
+            None => return false
+        };
+
+        self.node_mut(slot).visible = visible;
+        if !visible {
+            self.release_input_within(slot);
+        }
+        true
+    }
+
+    pub fn is_visible(&self, handle: WidgetHandle) -> bool {
+        match self.find_slot(handle) {
+            Some(slot) => self.is_shown(slot),
+            None => false
+        }
+    }
+
+/***********************************************************************************
+ *      UI::set_enabled
+ *      UI::is_enabled
+ *      
+ *      Disabled widgets and their children are still rendered, with the
+ *      disabled flag set in their RenderJob, but ignore clicks and can't
+ *      take keyboard focus. is_enabled is only true if the widget and all of
+ *      its parents are enabled and visible.
+ *      set_enabled returns false if the widget was removed.
+ ***********************************************************************************/
+    pub fn set_enabled(&mut self, handle: WidgetHandle, enabled: bool) -> bool {
+        let slot = match self.find_slot(handle) {
+            Some(slot) => slot,
+            None => return false
+        };
+
+        self.node_mut(slot).enabled = enabled;
+        if !enabled {
+            self.release_input_within(slot);
+        }
+        true
+    }
+
+    pub fn is_enabled(&self, handle: WidgetHandle) -> bool {
+        match self.find_slot(handle) {
+            Some(slot) => self.is_usable(slot),
+            None => false
+        }
+    }
+
+/***********************************************************************************
+ *      UI::set_name
+ *      UI::name
+ *      
+ *      An optional name to find the widget by. Names can't contain '/' and
+ *      an empty name removes it. Siblings may share a name, see UI::find.
+ *      set_name returns false if the name is invalid or the widget was
+ *      removed.
+ ***********************************************************************************/
+    pub fn set_name(&mut self, handle: WidgetHandle, name: &str) -> bool {
+        if name.contains('/') {return false;}
+
+        let slot = match self.find_slot(handle) {
+            Some(slot) => slot,
+            None => return false
+        };
+
+        self.node_mut(slot).name = if name.is_empty() { None } else { Some(name.to_owned()) };
+        true
+    }
+
+    pub fn name(&self, handle: WidgetHandle) -> Option<&str> {
+        match self.find_slot(handle) {
+            Some(slot) => self.node(slot).name.as_ref().map(|name| name.as_str()),
+            None => None
+        }
+    }
+
+/***********************************************************************************
+ *      UI::find
+ *      UI::find_in
+ *      
+ *      Looks up a widget by a path of names separated by '/', for example
+ *      "main_menu/quit". find starts at the root windows and find_in at the
+ *      children of parent. If siblings share a name the one created first is
+ *      found, no matter how they are stacked. Returns None if no widget
+ *      matches the path.
+ ***********************************************************************************/
+    pub fn find(&self, path: &str) -> Option<WidgetHandle> {
+        self.find_in(Parent::Root, path)
+    }
+
+    pub fn find_in<P: Into<Parent>>(&self, parent: P, path: &str) -> Option<WidgetHandle> {
+        let mut current = parent.into();
+        if let Parent::Widget(handle) = current {
+            if !self.contains(handle) {return None;}
+        }
+
+        for segment in path.split('/') {
+            let found = self.children(current)
+                .filter(|child| self.name(*child) == Some(segment))
+                .min_by_key(|child| self.node(self.slot_of(*child)).serial);
+            match found {
+                Some(child) => current = Parent::Widget(child),
+                None => return None
+            }
+        }
+
+        match current {
+            Parent::Widget(handle) => Some(handle),
+            Parent::Root => None
+        }
+    }
+
+/***********************************************************************************
+ *      UI::text
+ *      UI::set_text
+ *      
+ *      The text of a label, button or text box. Setting the text of a text
+ *      box moves the caret to the end and doesn't push a TextChanged event.
+ *      Returns false if the widget has no text or was removed.
+ ***********************************************************************************/
+    pub fn text(&self, handle: WidgetHandle) -> Option<&str> {
+        match self.widget(handle) {
+            Some(&Widget::Label{ref text, ..}) |
+            Some(&Widget::Button{ref text, ..}) |
+            Some(&Widget::Textbox{ref text, ..}) => Some(text),
+            _ => None
+        }
+    }
+
+    pub fn set_text(&mut self, handle: WidgetHandle, new_text: &str) -> bool {
+        let slot = match self.find_slot(handle) {
+            Some(slot) => slot,
+            None => return false
+        };
+
+        match self.node_mut(slot).widget {
+            Widget::Label{ref mut text, ..} | Widget::Button{ref mut text, ..} => {
+                if *text == new_text {return true;}
+                *text = new_text.to_owned();
+            }
+            Widget::Textbox{ref mut text, ref mut caret, ref mut selection, ..} => {
+                *text = new_text.to_owned();
+                *caret = text.chars().count();
+                *selection = None;
+                return true; //Text boxes have a fixed standard size
+            }
+            _ => return false
+        }
+
+        self.mark_layout_dirty(slot);
+        true
+    }
+
+/***********************************************************************************
+ *      UI::set_title
+ *      
+ *      Changes the title of a form.
+ *      Returns false if the widget isn't a form or was removed.
+ ***********************************************************************************/
+    pub fn set_title(&mut self, handle: WidgetHandle, new_title: &str) -> bool {
+        let slot = match self.find_slot(handle) {
+            Some(slot) => slot,
+            None => return false
+        };
+
+        match self.node_mut(slot).widget {
+            Widget::Form{ref mut title, ..} => {
+                *title = new_title.to_owned();
+                true
+            }
+            _ => false
+        }
+    }
+
+/***********************************************************************************
+ *      UI::rect
+ *      UI::set_rect
+ *      
+ *      The position of a widget relative to its parent and its size, as
+ *      (x, y, width, height). Labels are as large as their text and only
+ *      take the new position. A layout the widget is placed in overrides
+ *      the rect the next time it's solved. A layout of the widget's own
+ *      children is solved again for the new size.
+ *      Returns None or false for removed widgets.
+ ***********************************************************************************/
+    pub fn rect(&self, handle: WidgetHandle) -> Option<(i32, i32, i32, i32)> {
+        let widget = match self.widget(handle) {
+            Some(widget) => widget,
+            None => return None
+        };
+
+        let (x, y) = get_widget_position(widget);
+        match get_widget_rect(widget) {
+            Some(rect) => Some((x, y, rect.w, rect.h)),
+            None => {
+                let (w, h) = self.size_hint(widget);
+                Some((x, y, w as i32, h as i32))
+            }
+        }
+    }
+
+    pub fn set_rect(&mut self, handle: WidgetHandle, x: i32, y: i32, w: i32, h: i32) -> bool {
+        let slot = match self.find_slot(handle) {
+            Some(slot) => slot,
+            None => return false
+        };
+
+        {
+            let widget = &mut self.node_mut(slot).widget;
+            set_widget_position(widget, x, y);
+            set_widget_size(widget, w, h);
+        }
+
+        //The children are placed again inside the new size
+        self.mark_own_layout_dirty(slot);
+        true
+    }
+
+/***********************************************************************************
+ *      UI::edit_text
+ *      
+ *      Applies an editing operation to a Textbox widget. Pushes a TextChanged
+ *      event if the text was modified and a TextSubmitted event on Submit,
+ *      the change callbacks run right away.
+ *      Returns false if the widget isn't a Textbox or was removed.
+ ***********************************************************************************/
+    pub fn edit_text(&mut self, handle: WidgetHandle, edit: TextEdit) -> bool {
+        let slot = match self.find_slot(handle) {
+            Some(slot) => slot,
+            None => return false
+        };
+
+        let result = match self.node_mut(slot).widget {
+            Widget::Textbox{ref mut text, ref mut caret, ref mut selection, ..} => textbox_edit(text, caret, selection, edit),
+            _ => return false
+        };
+
+        match result {
+            TextEditResult::Changed => self.events.push_back(WidgetEvent::TextChanged(handle)),
+            TextEditResult::Submitted => self.events.push_back(WidgetEvent::TextSubmitted(handle)),
+            TextEditResult::Unchanged => ()
+        }
+
+        self.dispatch_callbacks();
+        true
+    }
+
+/***********************************************************************************
+ *      UI::mousemove
+ *      
+ *      Handles a mouse move event
+ *      Drags widgets and handles widgets losing mouse focus
+ ***********************************************************************************/
+    pub fn mousemove(&mut self, last_mx: i32, last_my: i32, mx: i32, my: i32) {
+        if let Some(dragged) = self.dragged_window {
+            let slot = self.slot_of(dragged);
+            match self.node_mut(slot).widget {
+                Widget::Form{ref mut position, ..} => {
+                    position.x+= mx - last_mx;
+                    position.y+= my - last_my;
+                }
+                _ => ()
+            }
+
+            return ;
+        }
+
+        self.update_layouts().ok();
+
+        let focused_widgets = self.widgets_at(mx, my);
+
+        //Check if any widgets lost focus
+        let lost: Vec<WidgetHandle> = self.mouse_focused_widgets.iter()
+            .filter(|w| !focused_widgets.contains(w))
+            .cloned()
+            .collect();
+
+        for w in lost {
+            let slot = self.slot_of(w);
+            match self.node_mut(slot).widget {
+                Widget::Button{ref mut pressed, ..} => *pressed = false,
+                _ => ()
+            }
+        }
+
+
+        self.mouse_focused_widgets = focused_widgets;
+    }
+
+    pub fn move_widget_to_front(&mut self, handle: WidgetHandle) {
+        let slot = match self.find_slot(handle) {
+            Some(slot) => slot,
+            None => return
+        };
+
+        //The children are linked to the widget, so they follow it to the front
+        if self.node(slot).next_sibling.is_some() {
+            let parent = self.node(slot).parent;
+            self.unlink(slot);
+            self.link_last(slot, parent);
+        }
+    }
+
+/***********************************************************************************
+ *      UI::mousedown
+ *      
  *      Handles a mousedown event to the widgets in focus
  ***********************************************************************************/
     pub fn mousedown(&mut self) {
         let mut to_front = Vec::new();
+        let mut keyboard_focus = None;
 
-        println!("{:?}", self.mouse_focused_widgets);
-        for w in self.mouse_focused_widgets.iter().rev() {
-            let i = self.find_widget_index_by_handle(*w);
+        for w in self.mouse_focused_widgets.clone().into_iter().rev() {
+            let slot = self.slot_of(w);
+
+            //Disabled widgets swallow the click, nothing is raised and the focus stays
+            if !self.is_usable(slot) {return;}
         
             let mut block = false;
         
-            match self.widgets[i].1 {
-                Widget::Form{..} => { self.dragged_window = *w; println!("{}", *w); block = true; }
-                Widget::Button{ref mut pressed, ..} => { *pressed = true; block = true; }
+            match self.node_mut(slot).widget {
+                Widget::Form{..} => { self.dragged_window = Some(w); block = true; }
+                Widget::Button{ref mut pressed, ..} => { *pressed = true; keyboard_focus = Some(w); block = true; }
+                Widget::Textbox{..} => { keyboard_focus = Some(w); block = true; }
                 _ => ()
             }
 
-            to_front.push(i);
+            to_front.push(w);
 
             if block {break;}
         }
 
-        for i in to_front {
-            self.move_widget_to_front(i);
+        self.set_keyboard_focus(keyboard_focus);
+
+        for w in to_front {
+            self.move_widget_to_front(w);
         }
 
+        self.dispatch_callbacks();
     }
 
 /***********************************************************************************
  *      UI::mouseup
  *      
- *      Handles a mouseup event to the widgets in focus
+ *      Handles a mouseup event to the widgets in focus. Releasing a pressed
+ *      button pushes a ButtonClicked event.
  ***********************************************************************************/
     pub fn mouseup(&mut self) {
-        self.dragged_window = -1;
-
-        for w in self.mouse_focused_widgets.iter().rev() {
-            let i = self.find_widget_index_by_handle(*w);
-            match self.widgets[i].1 {
-                Widget::Button{ref mut pressed, ..} => { *pressed = false; return; }
+        self.dragged_window = None;
+
+        let mut clicked = None;
+        for w in self.mouse_focused_widgets.clone().into_iter().rev() {
+            let slot = self.slot_of(w);
+            match self.node_mut(slot).widget {
+                Widget::Button{ref mut pressed, ..} => {
+                    //The button is only clicked if the cursor is still over it
+                    if *pressed { clicked = Some(w); }
+                    *pressed = false;
+                    break;
+                }
                 _ => ()
             }
         }
+
+        if let Some(handle) = clicked {
+            self.events.push_back(WidgetEvent::ButtonClicked(handle));
+        }
+
+        self.dispatch_callbacks();
     }
 
 /***********************************************************************************
- *      UI::render
+ *      UI::keydown
+ *      UI::keyup
  *      
- *      Creates a list of all widgets render information in the form of the
- *      RenderJob enum.
- ***********************************************************************************/
-    pub fn render(&self) -> Vec<RenderJob> {
-        let mut render_jobs: Vec<RenderJob>;
-        render_jobs = Vec::new();
-
-        let mut last_rect = self.screen_rect.clone();
-        let mut last_depth = 0;
-        last_rect.w = last_rect.w * 2;
-        last_rect.h = last_rect.h * 2;
-
-        let mut rects: Vec<(Depth, Rect)>;
-        rects = Vec::new();
-        rects.push( (last_depth, last_rect) );
-
-        let mut index = 0; 
-        for widget in &self.widgets {
-            //If the new widget is a child widget of the last widget
-            if last_depth < widget.0  {
-                rects.push( (last_depth, last_rect) );
-            }
-            //If the new widget is higher in the tree
-            else if last_depth > widget.0  {
-                while last_depth != widget.0  {
-                    rects.pop();
-                    let (ld, lr) = rects.last().unwrap().clone();
-                    last_depth = ld;
-                    last_rect = lr;
-                }
-            }
-            //The widget has the same parent as the previous one
-            else {
-                let (_, lr) = rects.last().unwrap().clone();
-                last_rect = lr;
-            }
+ *      Handles key events from the host. Tab and Shift+Tab move the keyboard
+ *      focus. Other keys are offered to the widget with keyboard focus first
+ *      and then bubble up through its parents until a widget consumes them.
+ *      Enter and Space click a focused button. Forms consume the keys that
+ *      reach them and push a KeyPressed or KeyReleased event, pressing Escape
+ *      also pushes CloseRequested. Key releases take the same way as the
+ *      presses, only forms react to them.
+ *      Returns true if a widget consumed the key.
+ ***********************************************************************************/
+    pub fn keydown(&mut self, key: Key, modifiers: Modifiers) -> bool {
+        let consumed = self.route_keydown(key, modifiers);
+        self.dispatch_callbacks();
+        consumed
+    }
 
-            let rect = get_widget_rect(&widget.1);
-            
-            let final_rect = match rect {
-                Some(rect) => {
-                    let parent_rect = last_rect;
-                    
-                    let final_x = rect.x + parent_rect.x;
-                    let final_y = rect.y + parent_rect.y;
-                    let mut final_w = rect.w;
-                    let mut final_h = rect.h;
+    pub fn keyup(&mut self, key: Key, modifiers: Modifiers) -> bool {
+        let consumed = self.route_keyup(key, modifiers);
+        self.dispatch_callbacks();
+        consumed
+    }
 
-                    if rect.x + rect.w > parent_rect.w {
-                        final_w = parent_rect.w - final_x;
-                    }
+    fn route_keydown(&mut self, key: Key, modifiers: Modifiers) -> bool {
+        if key == Key::Tab {
+            self.move_keyboard_focus(!modifiers.shift);
+            return self.keyboard_focus.is_some();
+        }
 
-                    if rect.y + rect.h > parent_rect.h {
-                        final_h = parent_rect.h - final_y;
-                    }
-                    Rect {
-                        x: final_x,
-                        y: final_y,
-                        w: final_w,
-                        h: final_h
+        let handle = match self.keyboard_focus {
+            Some(handle) => handle,
+            None => return false
+        };
+
+        let mut slot = Some(self.slot_of(handle));
+
+        while let Some(s) = slot {
+            let target = self.handle_of(s);
+
+            let consumed = match self.node(s).widget {
+                Widget::Textbox{..} => {
+                    match textbox_key_edit(key, modifiers) {
+                        Some(edit) => self.edit_text(target, edit),
+                        None => false
                     }
                 }
-                None => {
-                    let parent_rect = last_rect;
-                    let (ix, iy) = get_widget_position(&widget.1);
-
-                    Rect {
-                        x: ix + parent_rect.x, 
-                        y: iy + parent_rect.y, 
-                        w: parent_rect.w - ix, 
-                        h: parent_rect.h - iy 
+                Widget::Button{..} if key == Key::Enter || key == Key::Space => {
+                    self.events.push_back(WidgetEvent::ButtonClicked(target));
+                    true
+                }
+                Widget::Form{..} => {
+                    self.events.push_back(WidgetEvent::KeyPressed(target, key, modifiers));
+                    if key == Key::Escape {
+                        self.events.push_back(WidgetEvent::CloseRequested(target));
                     }
+                    true
                 }
+                _ => false
             };
 
-            let widget_handle = self.find_widget_handle_by_index(index);
+            if consumed {return true;}
+
+            slot = self.node(s).parent;
+        }
+
+        false
+    }
+
+    fn route_keyup(&mut self, key: Key, modifiers: Modifiers) -> bool {
+        //The press already moved the focus
+        if key == Key::Tab {
+            return self.keyboard_focus.is_some();
+        }
+
+        let handle = match self.keyboard_focus {
+            Some(handle) => handle,
+            None => return false
+        };
+
+        let mut slot = Some(self.slot_of(handle));
 
-            let mut focus = false;
-            for i in &self.mouse_focused_widgets {
-                if *i == widget_handle {
-                    focus = true; 
-                    break; 
+        while let Some(s) = slot {
+            let target = self.handle_of(s);
+
+            let consumed = match self.node(s).widget {
+                Widget::Form{..} => {
+                    self.events.push_back(WidgetEvent::KeyReleased(target, key, modifiers));
+                    true
                 }
-            }
+                _ => false
+            };
+
+            if consumed {return true;}
+
+            slot = self.node(s).parent;
+        }
+
+        false
+    }
+
+/***********************************************************************************
+ *      UI::text_input
+ *      
+ *      Handles text typed by the user, already translated by the host's
+ *      keyboard layout. The text is inserted into the focused Textbox.
+ *      Returns true if a widget consumed the text.
+ ***********************************************************************************/
+    pub fn text_input(&mut self, text: &str) -> bool {
+        match self.keyboard_focus {
+            Some(handle) => self.edit_text(handle, TextEdit::Insert(text.to_owned())),
+            None => false
+        }
+    }
+
+/***********************************************************************************
+ *      UI::render
+ *      
+ *      Creates a list of all widgets render information in the form of the
+ *      RenderJob enum. Layouts that need it are solved again first.
+ ***********************************************************************************/
+    pub fn render(&mut self) -> Vec<RenderJob> {
+        self.update_layouts().ok();
+
+        let mut render_jobs: Vec<RenderJob> = Vec::new();
 
-            match widget.1 {
+        self.walk_screen_rects(&mut |slot, final_rect, enabled| {
+            let disabled = !enabled;
+            let widget_handle = self.handle_of(slot);
+            let hovered = self.mouse_focused_widgets.contains(&widget_handle);
+            let keyboard_focused = self.keyboard_focus == Some(widget_handle);
+
+            match self.node(slot).widget {
                 Widget::Form{ref title, ..} => {
-                    render_jobs.push(render_form(widget_handle, focus, final_rect, &title));
+                    render_jobs.push(render_form(widget_handle, hovered, self.has_keyboard_focus_within(slot), disabled, final_rect, &title));
                 }
                 Widget::Label{ref text, ..} => {
-                    render_jobs.push(render_text(widget_handle, final_rect.x, final_rect.y, text));
+                    render_jobs.push(render_text(widget_handle, disabled, final_rect.x, final_rect.y, text));
                 }
                 Widget::Button{ref text, pressed, ..} => {
-                    render_jobs.push(render_button(widget_handle, pressed, focus, final_rect, text));
+                    render_jobs.push(render_button(widget_handle, pressed, hovered, keyboard_focused, disabled, final_rect, text));
+                }
+                Widget::Textbox{ref text, caret, selection, ..} => {
+                    render_jobs.push(render_textbox(widget_handle, hovered, keyboard_focused, disabled, final_rect, text, caret, selection));
                 }
                 _ => ()
             }
-
-            last_rect = final_rect;
-            last_depth = widget.0;
-            index+= 1;
-        }
+            true
+        });
 
         render_jobs
     }
@@ -709,7 +2363,7 @@ impl UI {
  *      Number of created widgets in the UI
  ***********************************************************************************/
     pub fn num_widgets(&self) -> usize {
-        self.widgets.len()
+        self.widget_count
     }
 }
 
@@ -726,17 +2380,17 @@ mod tests {
     fn test_gui_rendering() {
        
         let mut ui = UI::new(800, 600);
-        let main_form = ui.add_widget(-1, new_form(50, 50, 400, 300, "Test menu"));
-        let second_form = ui.add_widget(-1, new_form(100, 100, 200, 200, "Second form"));
-        let main_label = ui.add_widget(main_form, new_label(10, 10, "Hello."));
-        let main_button = ui.add_widget(main_form, new_button(10, 40, 100, 40, "OK."));
+        let main_form = ui.add_widget(Parent::Root, new_form(50, 50, 400, 300, "Test menu")).unwrap();
+        let second_form = ui.add_widget(Parent::Root, new_form(100, 100, 200, 200, "Second form")).unwrap();
+        let main_label = ui.add_widget(main_form, new_label(10, 10, "Hello.")).unwrap();
+        let main_button = ui.add_widget(main_form, new_button(10, 40, 100, 40, "OK.")).unwrap();
 
         let jobs = ui.render();
         assert!(jobs.len() == 4);
 
         for job in jobs {
             match job {
-                RenderJob::Form { index, focus, x, y, w, h, ref title} => {
+                RenderJob::Form { index, hovered, keyboard_focused, disabled, x, y, w, h, ref title} => {
                     //println!("{}", index);
                     assert!(index == main_form || index == second_form);
                     
@@ -754,7 +2408,7 @@ mod tests {
                     }
                     
                 }
-                RenderJob::Button {index, focus, pressed, x, y, w, h, ref text} =>  {
+                RenderJob::Button {index, hovered, keyboard_focused, disabled, pressed, x, y, w, h, ref text} =>  {
                     assert_eq!(index, main_button);
 
                     assert_eq!(x, 60);
@@ -770,4 +2424,686 @@ mod tests {
         ui.mousemove(0, 0, 80, 110);
         ui.mousedown();
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_textbox_editing() {
+        let mut ui = UI::new(800, 600);
+        let form = ui.add_widget(Parent::Root, new_form(50, 50, 400, 300, "Settings")).unwrap();
+        let textbox = ui.add_widget(form, new_textbox(10, 10, 250, 30, "Hero")).unwrap();
+        let label = ui.add_widget(form, new_label(10, 50, "Name")).unwrap();
+
+        assert!(ui.edit_text(textbox, TextEdit::Insert("ine".to_owned())));
+        assert!(ui.edit_text(textbox, TextEdit::Move{motion: CaretMotion::Home, select: false}));
+        assert!(ui.edit_text(textbox, TextEdit::Delete));
+        assert!(ui.edit_text(textbox, TextEdit::Insert("h\n".to_owned())));
+        assert!(ui.edit_text(textbox, TextEdit::Move{motion: CaretMotion::End, select: true}));
+        assert!(ui.edit_text(textbox, TextEdit::Move{motion: CaretMotion::Left, select: true}));
+        assert!(ui.edit_text(textbox, TextEdit::Backspace));
+        assert!(ui.edit_text(textbox, TextEdit::Submit));
+        assert!(!ui.edit_text(label, TextEdit::Backspace));
+
+        let mut found = false;
+        for job in ui.render() {
+            if let RenderJob::Textbox{index, ref text, caret, selection, ..} = job {
+                assert_eq!(index, textbox);
+                assert_eq!(text, "he");
+                assert_eq!(caret, 1);
+                assert_eq!(selection, None);
+                found = true;
+            }
+        }
+        assert!(found);
+
+        let changes = ui.events.iter().filter(|e| match **e { WidgetEvent::TextChanged(h) => h == textbox, _ => false }).count();
+        let submits = ui.events.iter().filter(|e| match **e { WidgetEvent::TextSubmitted(h) => h == textbox, _ => false }).count();
+        assert_eq!(changes, 4);
+        assert_eq!(submits, 1);
+    }
+
+    #[test]
+    fn test_keyboard_input() {
+        let mut ui = UI::new(800, 600);
+        let form = ui.add_widget(Parent::Root, new_form(50, 50, 400, 300, "Settings")).unwrap();
+        let textbox = ui.add_widget(form, new_textbox(10, 10, 250, 30, "")).unwrap();
+
+        //Nothing has keyboard focus yet
+        assert!(!ui.text_input("x"));
+        assert!(!ui.keydown(Key::Escape, Modifiers::none()));
+        assert!(!ui.keyup(Key::Escape, Modifiers::none()));
+
+        ui.mousemove(0, 0, 70, 70);
+        ui.mousedown();
+        ui.mouseup();
+
+        assert!(ui.text_input("Gold"));
+        assert!(ui.keydown(Key::Left, Modifiers::shift()));
+        assert!(ui.keydown(Key::Backspace, Modifiers::none()));
+        assert!(ui.text_input("ld"));
+        assert!(ui.keydown(Key::Escape, Modifiers::none()));
+        assert!(ui.keyup(Key::Escape, Modifiers::none()));
+
+        for job in ui.render() {
+            if let RenderJob::Textbox{ref text, ..} = job {
+                assert_eq!(text, "Golld");
+            }
+        }
+
+        let escapes = ui.events.iter().filter(|e| match **e {
+            WidgetEvent::KeyPressed(h, Key::Escape, _) => h == form,
+            _ => false
+        }).count();
+        assert_eq!(escapes, 1);
+
+        //The textbox doesn't react to the release, it bubbles up to the form
+        let releases = ui.events.iter().filter(|e| match **e {
+            WidgetEvent::KeyReleased(h, Key::Escape, _) => h == form,
+            _ => false
+        }).count();
+        assert_eq!(releases, 1);
+    }
+
+    #[test]
+    fn test_keyboard_focus_traversal() {
+        let mut ui = UI::new(800, 600);
+        let form = ui.add_widget(Parent::Root, new_form(50, 50, 400, 300, "Settings")).unwrap();
+        let name = ui.add_widget(form, new_textbox(10, 10, 250, 30, "")).unwrap();
+        let label = ui.add_widget(form, new_label(10, 50, "Name")).unwrap();
+        let ok = ui.add_widget(form, new_button(10, 90, 100, 40, "OK.")).unwrap();
+        let cancel = ui.add_widget(form, new_button(120, 90, 100, 40, "Cancel")).unwrap();
+
+        assert!(!ui.set_keyboard_focus(Some(label)));
+        assert_eq!(ui.keyboard_focus(), None);
+
+        assert!(ui.keydown(Key::Tab, Modifiers::none()));
+        assert_eq!(ui.keyboard_focus(), Some(name));
+        ui.keydown(Key::Tab, Modifiers::none());
+        assert_eq!(ui.keyboard_focus(), Some(ok));
+        ui.keydown(Key::Tab, Modifiers::none());
+        ui.keydown(Key::Tab, Modifiers::none());
+        assert_eq!(ui.keyboard_focus(), Some(name));
+        ui.keydown(Key::Tab, Modifiers::shift());
+        assert_eq!(ui.keyboard_focus(), Some(cancel));
+
+        let lost = ui.events.iter().filter(|e| match **e { WidgetEvent::FocusLost(_) => true, _ => false }).count();
+        let gained = ui.events.iter().filter(|e| match **e { WidgetEvent::FocusGained(_) => true, _ => false }).count();
+        assert_eq!(lost, 4);
+        assert_eq!(gained, 5);
+        ui.clear_events();
+
+        ui.mousemove(0, 0, 70, 150);
+        for job in ui.render() {
+            match job {
+                RenderJob::Form{hovered, keyboard_focused, ..} => { assert!(hovered); assert!(keyboard_focused); }
+                RenderJob::Button{index, hovered, keyboard_focused, ..} => {
+                    assert_eq!(hovered, index == ok);
+                    assert_eq!(keyboard_focused, index == cancel);
+                }
+                _ => ()
+            }
+        }
+    }
+
+    #[test]
+    fn test_focus_order() {
+        let mut ui = UI::new(800, 600);
+        let form = ui.add_widget(Parent::Root, new_form(50, 50, 400, 300, "Login")).unwrap();
+        let ok = ui.add_widget(form, new_button(0, 0, 100, 40, "OK.")).unwrap();
+        let name = ui.add_widget(form, new_textbox(0, 0, 250, 30, "")).unwrap();
+        let password = ui.add_widget(form, new_textbox(0, 0, 250, 30, "")).unwrap();
+        let help = ui.add_widget(form, new_button(0, 0, 100, 40, "Help")).unwrap();
+
+        //The grid places the widgets in another order than they were created in
+        assert_eq!(ui.set_layout(form, &|ref mut l| {
+            l.add(name).wrap();
+            l.add(password).wrap();
+            l.add(ok);
+        }), Ok(()));
+
+        let mut order = Vec::new();
+        for _ in 0..4 {
+            ui.focus_next();
+            order.push(ui.keyboard_focus().unwrap());
+        }
+        assert_eq!(order, vec![name, password, ok, help]);
+
+        //Without a layout the stacking order is used
+        let dialog = ui.add_widget(Parent::Root, new_form(0, 0, 300, 200, "Dialog")).unwrap();
+        let yes = ui.add_widget(dialog, new_button(0, 0, 100, 40, "Yes")).unwrap();
+        let no = ui.add_widget(dialog, new_button(0, 0, 100, 40, "No")).unwrap();
+        assert_eq!(ui.reparent(yes, dialog, 2), Ok(()));
+        ui.set_keyboard_focus(Some(no));
+        ui.focus_next();
+        assert_eq!(ui.keyboard_focus(), Some(yes));
+        ui.focus_next();
+        assert_eq!(ui.keyboard_focus(), Some(no));
+    }
+
+    #[test]
+    fn test_button_clicked_events() {
+        let mut ui = UI::new(800, 600);
+        let form = ui.add_widget(Parent::Root, new_form(50, 50, 400, 300, "Main Menu")).unwrap();
+        let resume = ui.add_widget(form, new_button(10, 40, 200, 40, "Resume Quest")).unwrap();
+        let quit = ui.add_widget(form, new_button(10, 100, 200, 40, "Quit")).unwrap();
+
+        //Press and release over the button
+        ui.mousemove(0, 0, 70, 100);
+        ui.mousedown();
+        ui.mouseup();
+        assert_eq!(ui.poll_event(), Some(WidgetEvent::FocusGained(resume)));
+        assert_eq!(ui.poll_event(), Some(WidgetEvent::ButtonClicked(resume)));
+        assert_eq!(ui.poll_event(), None);
+
+        //Press, then drag the cursor away before releasing
+        ui.mousedown();
+        ui.mousemove(70, 100, 20, 20);
+        ui.mouseup();
+        assert_eq!(ui.poll_event(), None);
+
+        //Click the focused button with the keyboard
+        ui.keydown(Key::Tab, Modifiers::none());
+        assert!(ui.keydown(Key::Enter, Modifiers::none()));
+        let events: Vec<WidgetEvent> = ui.drain_events().collect();
+        assert_eq!(events, vec![WidgetEvent::FocusLost(resume),
+                                WidgetEvent::FocusGained(quit),
+                                WidgetEvent::ButtonClicked(quit)]);
+        assert_eq!(ui.poll_event(), None);
+    }
+
+    #[test]
+    fn test_widget_callbacks() {
+        use std::rc::Rc;
+        use std::cell::RefCell;
+
+        let mut ui = UI::new(800, 600);
+        let form = ui.add_widget(Parent::Root, new_form(50, 50, 400, 300, "Main Menu")).unwrap();
+        let credits = ui.add_widget(form, new_button(10, 40, 200, 40, "Credits")).unwrap();
+        let name = ui.add_widget(form, new_textbox(10, 100, 250, 30, "")).unwrap();
+
+        let log = Rc::new(RefCell::new(Vec::new()));
+
+        //Clicking credits opens a new root form from inside the callback
+        let click_log = log.clone();
+        ui.on_click(credits, move |ui, event| {
+            ui.add_widget(Parent::Root, new_form(500, 100, 200, 200, "Credits")).unwrap();
+            click_log.borrow_mut().push(format!("{:?}", event));
+        });
+
+        let change_log = log.clone();
+        ui.on_change(name, move |_, event| change_log.borrow_mut().push(format!("{:?}", event)));
+
+        let close_log = log.clone();
+        ui.on_close(form, move |_, event| close_log.borrow_mut().push(format!("{:?}", event)));
+
+        ui.mousemove(0, 0, 70, 100);
+        ui.mousedown();
+        ui.mouseup();
+        assert_eq!(ui.num_widgets(), 4);
+
+        ui.mousemove(70, 100, 70, 160);
+        ui.mousedown();
+        ui.text_input("a");
+        ui.keydown(Key::Escape, Modifiers::none());
+
+        assert_eq!(*log.borrow(), vec![format!("{:?}", WidgetEvent::ButtonClicked(credits)),
+                                       format!("{:?}", WidgetEvent::TextChanged(name)),
+                                       format!("{:?}", WidgetEvent::CloseRequested(form))]);
+
+        //Edits by the application run the callbacks even if the events are drained right after
+        assert!(ui.edit_text(name, TextEdit::Backspace));
+        ui.drain_events();
+        assert_eq!(log.borrow().len(), 4);
+        assert_eq!(log.borrow()[3], format!("{:?}", WidgetEvent::TextChanged(name)));
+    }
+
+    #[test]
+    fn test_remove_widget_subtree() {
+        let mut ui = UI::new(800, 600);
+        let main_form = ui.add_widget(Parent::Root, new_form(50, 50, 400, 300, "Main Menu")).unwrap();
+        let dialog = ui.add_widget(Parent::Root, new_form(100, 100, 200, 200, "Dialog")).unwrap();
+        let dialog_label = ui.add_widget(dialog, new_label(10, 10, "Really quit?")).unwrap();
+        let dialog_button = ui.add_widget(dialog, new_button(10, 40, 100, 40, "Yes")).unwrap();
+        let main_button = ui.add_widget(main_form, new_button(10, 40, 100, 40, "OK.")).unwrap();
+
+        //Hover and focus widgets inside the dialog
+        ui.mousemove(0, 0, 120, 150);
+        ui.mousedown();
+        assert_eq!(ui.keyboard_focus(), Some(dialog_button));
+        ui.on_click(dialog_button, |_, _| ());
+
+        assert!(ui.remove_widget(dialog));
+        assert!(!ui.remove_widget(dialog));
+        assert!(!ui.contains(dialog_label));
+        assert!(!ui.contains(dialog_button));
+        assert!(ui.contains(main_button));
+        assert_eq!(ui.num_widgets(), 2);
+        assert_eq!(ui.keyboard_focus(), None);
+        assert!(ui.mouse_focused_widgets.is_empty());
+        assert!(ui.callbacks.is_empty());
+        assert_eq!(ui.dragged_window, None);
+
+        let handles: Vec<WidgetHandle> = ui.render().iter().filter_map(|job| job.handle()).collect();
+        assert_eq!(handles, vec![main_form, main_button]);
+
+        //New widgets may reuse the slots, but never the removed handles
+        let label = ui.add_widget(main_form, new_label(10, 10, "Hello.")).unwrap();
+        assert!(label != dialog && label != dialog_label && label != dialog_button);
+        assert!(ui.widget(dialog_button).is_none());
+        assert!(ui.widget(label).is_some());
+        assert!(!ui.edit_text(dialog_button, TextEdit::Backspace));
+        assert!(!ui.set_keyboard_focus(Some(dialog_button)));
+        assert!(ui.remove_widget(main_button));
+        let handles: Vec<WidgetHandle> = ui.render().iter().filter_map(|job| job.handle()).collect();
+        assert_eq!(handles, vec![main_form, label]);
+    }
+
+    #[test]
+    fn test_ui_errors() {
+        let mut ui = UI::new(800, 600);
+        let form = ui.add_widget(Parent::Root, new_form(50, 50, 400, 300, "Main Menu")).unwrap();
+        let other = ui.add_widget(Parent::Root, new_form(100, 100, 200, 200, "Other")).unwrap();
+        let label = ui.add_widget(form, new_label(10, 10, "Hello.")).unwrap();
+        let button = ui.add_widget(other, new_button(10, 40, 100, 40, "OK.")).unwrap();
+        let dialog = ui.add_widget(Parent::Root, new_form(100, 100, 200, 200, "Dialog")).unwrap();
+        ui.remove_widget(dialog);
+
+        assert_eq!(ui.add_widget(dialog, new_label(0, 0, "")), Err(UiError::StaleHandle(dialog)));
+        assert_eq!(ui.set_layout(dialog, &|ref mut l| { l.add(label); }), Err(UiError::StaleHandle(dialog)));
+        assert_eq!(ui.set_layout(form, &|ref mut l| { l.add(label).add(button); }),
+                   Err(UiError::NotAChild{parent: form, child: button}));
+        assert_eq!(ui.set_layout(form, &|ref mut l| { l.add(label).span(1, 1).wrap(); l.span(2, 1); }),
+                   Err(UiError::Layout(layout::LayoutError::NoCellToSpan{column: 0, row: 1})));
+        assert_eq!(ui.set_layout(form, &|ref mut l| { l.add(label); }), Ok(()));
+    }
+
+    #[test]
+    fn test_widget_stacking_order() {
+        let mut ui = UI::new(800, 600);
+        let back = ui.add_widget(Parent::Root, new_form(0, 0, 400, 300, "Back")).unwrap();
+        let front = ui.add_widget(Parent::Root, new_form(50, 50, 400, 300, "Front")).unwrap();
+        let first = ui.add_widget(back, new_button(10, 10, 100, 40, "First")).unwrap();
+        let second = ui.add_widget(back, new_button(10, 60, 100, 40, "Second")).unwrap();
+
+        let order = |ui: &mut UI| -> Vec<WidgetHandle> { ui.render().iter().filter_map(|job| job.handle()).collect() };
+
+        //Parents are drawn before their children and later widgets on top
+        assert_eq!(order(&mut ui), vec![back, first, second, front]);
+
+        //Raising a window brings its children along
+        ui.move_widget_to_front(back);
+        assert_eq!(order(&mut ui), vec![front, back, first, second]);
+        ui.move_widget_to_front(first);
+        assert_eq!(order(&mut ui), vec![front, back, second, first]);
+
+        assert!(ui.remove_widget(second));
+        let third = ui.add_widget(back, new_button(10, 60, 100, 40, "Third")).unwrap();
+        assert_eq!(order(&mut ui), vec![front, back, first, third]);
+        assert_eq!(ui.num_widgets(), 4);
+
+        //Tab order follows creation even when slots are reused
+        ui.focus_next();
+        assert_eq!(ui.keyboard_focus(), Some(first));
+        ui.focus_next();
+        assert_eq!(ui.keyboard_focus(), Some(third));
+    }
+
+    #[test]
+    fn test_tree_queries() {
+        let mut ui = UI::new(800, 600);
+        let form = ui.add_widget(Parent::Root, new_form(50, 50, 400, 300, "Inventory")).unwrap();
+        let other = ui.add_widget(Parent::Root, new_form(500, 50, 200, 200, "Other")).unwrap();
+        let panel = ui.add_widget(form, new_form(10, 30, 200, 200, "Panel")).unwrap();
+        let button = ui.add_widget(panel, new_button(10, 10, 100, 40, "Use")).unwrap();
+        let label = ui.add_widget(form, new_label(250, 30, "Gold")).unwrap();
+
+        assert_eq!(ui.parent(button), Some(panel));
+        assert_eq!(ui.parent(form), None);
+        assert_eq!(ui.depth(button), Some(2));
+        assert_eq!(ui.children(Parent::Root).collect::<Vec<_>>(), vec![form, other]);
+        assert_eq!(ui.children(form).collect::<Vec<_>>(), vec![panel, label]);
+        assert_eq!(ui.descendants(form).collect::<Vec<_>>(), vec![panel, button, label]);
+        assert_eq!(ui.ancestors(button).collect::<Vec<_>>(), vec![panel, form]);
+
+        //Hovering uses the same rects as rendering, clipped to the parent
+        ui.mousemove(0, 0, 75, 95);
+        let jobs = ui.render();
+        let hovered: Vec<WidgetHandle> = jobs.iter().filter_map(|job| match *job {
+            RenderJob::Form{index, hovered: true, ..} | RenderJob::Button{index, hovered: true, ..} => Some(index),
+            _ => None
+        }).collect();
+        assert_eq!(hovered, vec![form, panel, button]);
+
+        assert!(ui.remove_widget(panel));
+        assert_eq!(ui.parent(button), None);
+        assert_eq!(ui.depth(button), None);
+        assert_eq!(ui.children(panel).count(), 0);
+        assert_eq!(ui.descendants(form).collect::<Vec<_>>(), vec![label]);
+    }
+
+    #[test]
+    fn test_hit_testing() {
+        let mut ui = UI::new(800, 600);
+        let back = ui.add_widget(Parent::Root, new_form(0, 0, 400, 300, "Back")).unwrap();
+        let button = ui.add_widget(back, new_button(10, 10, 100, 40, "Back button")).unwrap();
+        let clipped = ui.add_widget(back, new_button(350, 10, 100, 40, "Clipped")).unwrap();
+        let front = ui.add_widget(Parent::Root, new_form(200, 0, 300, 300, "Front")).unwrap();
+        let label = ui.add_widget(front, new_label(10, 10, "Gold: 120")).unwrap();
+
+        assert_eq!(ui.widget_at(20, 20), Some(button));
+        assert_eq!(ui.widgets_at(20, 20), vec![back, button]);
+
+        //The front form covers the back form, labels are never hit
+        assert_eq!(ui.widgets_at(360, 20), vec![front]);
+        ui.move_widget_to_front(back);
+        assert_eq!(ui.widgets_at(360, 20), vec![back, clipped]);
+
+        //Children are clipped to their parent
+        assert_eq!(ui.widget_at(420, 20), Some(front));
+        assert_eq!(ui.widget_at(700, 500), None);
+
+        //Queries don't change the hover state
+        assert!(ui.mouse_focused_widgets.is_empty());
+        ui.mousemove(0, 0, 20, 20);
+        assert_eq!(ui.mouse_focused_widgets, ui.widgets_at(20, 20));
+    }
+
+    #[test]
+    fn test_widget_properties() {
+        let mut ui = UI::new(800, 600);
+        let hud = ui.add_widget(Parent::Root, new_form(0, 0, 300, 300, "HUD")).unwrap();
+        let gold = ui.add_widget(hud, new_label(0, 0, "Gold: 0")).unwrap();
+        let button = ui.add_widget(hud, new_button(0, 0, 100, 40, "Shop")).unwrap();
+        let name = ui.add_widget(hud, new_textbox(0, 0, 250, 30, "Hero")).unwrap();
+
+        assert_eq!(ui.set_layout(hud, &|ref mut l| { l.add(gold).wrap(); l.add(button); }), Ok(()));
+        let laid_out = ui.rect(button).unwrap();
+
+        assert!(ui.set_text(gold, "Gold: 120"));
+        assert_eq!(ui.text(gold), Some("Gold: 120"));
+        assert!(ui.set_title(hud, "Status"));
+        assert!(!ui.set_title(gold, "Status"));
+        assert_eq!(ui.text(hud), None);
+
+        assert!(ui.set_text(name, "Heroine"));
+        match ui.widget(name) {
+            Some(&Widget::Textbox{caret, selection, ..}) => assert_eq!((caret, selection), (7, None)),
+            _ => panic!("Textbox expected")
+        }
+
+        assert!(ui.set_rect(button, 5, 6, 70, 20));
+        assert_eq!(ui.rect(button), Some((5, 6, 70, 20)));
+
+        //The changed label marked the layout dirty, so rendering places the button again
+        ui.render();
+        assert_eq!(ui.rect(button), Some(laid_out));
+
+        //Removed widgets are taken out of the layout
+        assert!(ui.remove_widget(gold));
+        ui.set_rect(button, 5, 6, 70, 20);
+        assert_eq!(ui.update_layouts(), Ok(()));
+        assert_ne!(ui.rect(button), Some((5, 6, 70, 20)));
+
+        assert!(!ui.set_text(gold, "Gold: 0"));
+        assert_eq!(ui.rect(gold), None);
+    }
+
+    #[test]
+    fn test_disabled_click() {
+        let mut ui = UI::new(800, 600);
+        let shop = ui.add_widget(Parent::Root, new_form(0, 0, 200, 200, "Shop")).unwrap();
+        let chat = ui.add_widget(Parent::Root, new_form(300, 0, 200, 200, "Chat")).unwrap();
+        let message = ui.add_widget(chat, new_textbox(10, 30, 150, 30, "")).unwrap();
+        assert!(ui.set_keyboard_focus(Some(message)));
+        assert!(ui.set_enabled(shop, false));
+
+        //Clicking the disabled form neither raises it nor takes the focus away
+        ui.mousemove(0, 0, 50, 50);
+        ui.mousedown();
+        ui.mouseup();
+        assert_eq!(ui.children(Parent::Root).collect::<Vec<_>>(), vec![shop, chat]);
+        assert_eq!(ui.keyboard_focus(), Some(message));
+    }
+
+    #[test]
+    fn test_visible_and_enabled() {
+        let mut ui = UI::new(800, 600);
+        let form = ui.add_widget(Parent::Root, new_form(0, 0, 400, 300, "Loot")).unwrap();
+        let take = ui.add_widget(form, new_button(10, 10, 100, 40, "Take")).unwrap();
+        let name = ui.add_widget(form, new_textbox(10, 60, 250, 30, "Sword")).unwrap();
+        let popup = ui.add_widget(Parent::Root, new_form(0, 0, 200, 200, "Popup")).unwrap();
+
+        //A hidden form doesn't render or take hover, the form below gets the mouse
+        assert!(ui.set_visible(popup, false));
+        assert!(!ui.is_visible(popup));
+        assert_eq!(ui.render().len(), 3);
+        assert_eq!(ui.widget_at(20, 20), Some(take));
+
+        //Disabled widgets render with the flag, ignore clicks and keyboard focus
+        assert!(ui.set_keyboard_focus(Some(name)));
+        assert!(ui.set_enabled(form, false));
+        assert_eq!(ui.keyboard_focus(), None);
+        assert!(!ui.is_enabled(take));
+        assert!(!ui.set_keyboard_focus(Some(take)));
+
+        ui.mousemove(0, 0, 20, 20);
+        ui.mousedown();
+        ui.mouseup();
+        assert_eq!(ui.keyboard_focus(), None);
+        assert!(!ui.drain_events().any(|e| e == WidgetEvent::ButtonClicked(take)));
+
+        for job in ui.render() {
+            match job {
+                RenderJob::Form{disabled, ..} | RenderJob::Button{disabled, ..} | RenderJob::Textbox{disabled, ..} => assert!(disabled),
+                _ => ()
+            }
+        }
+
+        ui.focus_next();
+        assert_eq!(ui.keyboard_focus(), None);
+
+        assert!(ui.set_enabled(form, true));
+        ui.focus_next();
+        assert_eq!(ui.keyboard_focus(), Some(take));
+
+        //Hiding the focused subtree drops the keyboard focus
+        assert!(ui.set_visible(form, false));
+        assert_eq!(ui.keyboard_focus(), None);
+        assert!(!ui.is_visible(take));
+        assert_eq!(ui.widget_at(20, 20), None);
+    }
+
+    #[test]
+    fn test_named_widgets() {
+        let mut ui = UI::new(800, 600);
+        let menu = ui.add_widget(Parent::Root, new_form(0, 0, 400, 300, "Main Menu")).unwrap();
+        let quit = ui.add_widget(menu, new_button(10, 10, 100, 40, "Quit")).unwrap();
+        let options = ui.add_widget(menu, new_form(10, 60, 200, 200, "Options")).unwrap();
+        let back = ui.add_widget(options, new_button(10, 10, 100, 40, "Back")).unwrap();
+
+        assert!(ui.set_name(menu, "main_menu"));
+        assert!(ui.set_name(quit, "quit"));
+        assert!(ui.set_name(options, "options"));
+        assert!(ui.set_name(back, "quit"));
+        assert!(!ui.set_name(back, "options/back"));
+        assert_eq!(ui.name(back), Some("quit"));
+
+        assert_eq!(ui.find("main_menu/quit"), Some(quit));
+        assert_eq!(ui.find("main_menu/options/quit"), Some(back));
+        assert_eq!(ui.find_in(menu, "options/quit"), Some(back));
+        assert_eq!(ui.find_in(options, "quit"), Some(back));
+        assert_eq!(ui.find("quit"), None);
+        assert_eq!(ui.find("main_menu/"), None);
+        assert_eq!(ui.find(""), None);
+
+        //Twins are found in creation order, raising one doesn't change that
+        let twin = ui.add_widget(menu, new_button(10, 10, 100, 40, "Quit")).unwrap();
+        assert!(ui.set_name(twin, "quit"));
+        ui.move_widget_to_front(twin);
+        assert_eq!(ui.find("main_menu/quit"), Some(quit));
+        ui.move_widget_to_front(quit);
+        assert_eq!(ui.find("main_menu/quit"), Some(quit));
+        assert!(ui.remove_widget(twin));
+
+        assert!(ui.set_name(quit, ""));
+        assert_eq!(ui.name(quit), None);
+        assert_eq!(ui.find("main_menu/quit"), None);
+
+        assert!(ui.remove_widget(options));
+        assert_eq!(ui.find("main_menu/options/quit"), None);
+        assert_eq!(ui.find_in(options, "quit"), None);
+    }
+
+    #[test]
+    fn test_reparent() {
+        let mut ui = UI::new(800, 600);
+        let loot = ui.add_widget(Parent::Root, new_form(0, 0, 300, 300, "Loot")).unwrap();
+        let inventory = ui.add_widget(Parent::Root, new_form(400, 0, 300, 300, "Inventory")).unwrap();
+        let potion = ui.add_widget(inventory, new_button(10, 10, 100, 40, "Potion")).unwrap();
+        let sword = ui.add_widget(loot, new_form(10, 10, 150, 100, "Sword")).unwrap();
+        let take = ui.add_widget(sword, new_button(10, 10, 100, 40, "Take")).unwrap();
+        let gold = ui.add_widget(loot, new_label(0, 0, "Gold")).unwrap();
+        assert_eq!(ui.set_layout(loot, &|ref mut l| { l.add(sword).add(gold); }), Ok(()));
+
+        assert_eq!(ui.reparent(sword, inventory, 0), Ok(()));
+        assert_eq!(ui.parent(sword), Some(inventory));
+        assert_eq!(ui.children(inventory).collect::<Vec<_>>(), vec![sword, potion]);
+        assert_eq!(ui.children(loot).collect::<Vec<_>>(), vec![gold]);
+        assert_eq!(ui.depth(take), Some(2));
+
+        //The subtree is drawn inside the new parent
+        ui.set_rect(sword, 10, 60, 150, 100);
+        assert_eq!(ui.render().iter().filter_map(|job| match *job {
+            RenderJob::Button{index, x, y, ..} if index == take => Some((x, y)),
+            _ => None
+        }).next(), Some((420, 70)));
+
+        //The loot layout let go of the sword and only places the label
+        ui.set_text(gold, "Gold: 5");
+        assert_eq!(ui.update_layouts(), Ok(()));
+        assert_eq!(ui.rect(sword), Some((10, 60, 150, 100)));
+
+        assert_eq!(ui.reparent(inventory, take, 0), Err(UiError::CyclicParent{parent: take, child: inventory}));
+        assert_eq!(ui.reparent(sword, sword, 0), Err(UiError::CyclicParent{parent: sword, child: sword}));
+
+        assert_eq!(ui.reparent(sword, Parent::Root, 10), Ok(()));
+        assert_eq!(ui.children(Parent::Root).collect::<Vec<_>>(), vec![loot, inventory, sword]);
+        assert_eq!(ui.depth(take), Some(1));
+
+        //A focused textbox moved into a hidden form can't be typed into anymore
+        let amount = ui.add_widget(sword, new_textbox(10, 60, 100, 30, "")).unwrap();
+        assert!(ui.set_keyboard_focus(Some(amount)));
+        assert!(ui.set_visible(loot, false));
+        assert_eq!(ui.reparent(amount, loot, 0), Ok(()));
+        assert_eq!(ui.keyboard_focus(), None);
+        assert!(!ui.text_input("z"));
+        assert_eq!(ui.text(amount), Some(""));
+
+        assert!(ui.remove_widget(potion));
+        assert_eq!(ui.reparent(potion, loot, 0), Err(UiError::StaleHandle(potion)));
+        assert_eq!(ui.reparent(take, potion, 0), Err(UiError::StaleHandle(potion)));
+    }
+
+    #[test]
+    fn test_text_measurer() {
+        struct FixedWidth;
+
+        impl TextMeasurer for FixedWidth {
+            fn line_width(&self, text: &str, font_size: u32) -> f64 {
+                (text.chars().count() as u32 * font_size / 2) as f64
+            }
+
+            fn line_height(&self, font_size: u32) -> f64 {
+                font_size as f64 * 1.5
+            }
+        }
+
+        let mut ui = UI::new(800, 600);
+        let form = ui.add_widget(Parent::Root, new_form(0, 0, 400, 300, "Shop")).unwrap();
+        let label = ui.add_widget(form, new_label(10, 10, "Gold: 120")).unwrap();
+        let button = ui.add_widget(form, new_button(10, 40, 0, 0, "Buy")).unwrap();
+
+        //Without a measurer the text size is estimated
+        assert_eq!(ui.rect(label), Some((10, 10, 81, 18)));
+
+        ui.set_text_measurer(Box::new(FixedWidth));
+        assert_eq!(ui.rect(label), Some((10, 10, 81, 27)));
+        assert_eq!(ui.size_hint(ui.widget(button).unwrap()), (100, 47));
+        ui.set_text(button, "Buy everything in the shop");
+        assert_eq!(ui.size_hint(ui.widget(button).unwrap()), (254, 47));
+    }
+
+    #[test]
+    fn test_grid_layout_size() {
+        let mut ui = UI::new(800, 600);
+        let menu = ui.add_widget(Parent::Root, new_form(50, 50, 400, 300, "Main Menu")).unwrap();
+        let ok = ui.add_widget(menu, new_button(0, 0, 0, 0, "OK.")).unwrap();
+        let cancel = ui.add_widget(menu, new_button(0, 0, 0, 0, "Cancel")).unwrap();
+        assert_eq!(ui.set_layout(menu, &|ref mut l| { l.add(ok).add(cancel); }), Ok(()));
+
+        //The grid spans the form inside the border and below the title bar
+        assert_eq!(ui.rect(ok), Some((15, 36, 181, 38)));
+        assert_eq!(ui.rect(cancel), Some((204, 36, 181, 38)));
+
+        //Resizing the form solves the grid again
+        assert!(ui.set_rect(menu, 50, 50, 500, 300));
+        ui.render();
+        assert_eq!(ui.rect(ok), Some((15, 36, 231, 38)));
+        assert_eq!(ui.rect(cancel), Some((254, 36, 231, 38)));
+    }
+
+    #[test]
+    fn test_nested_layouts() {
+        let mut ui = UI::new(800, 600);
+        let outer = ui.add_widget(Parent::Root, new_form(0, 0, 400, 300, "Outer")).unwrap();
+        assert_eq!(ui.set_box_layout(outer, Layout::Vertical), Ok(()));
+        let inner = ui.add_widget(outer, new_form(0, 0, 100, 100, "Inner")).unwrap();
+        assert_eq!(ui.set_box_layout(inner, Layout::Vertical), Ok(()));
+        let ok = ui.add_widget(inner, new_button(0, 0, 0, 0, "OK.")).unwrap();
+
+        //The inner form is stretched by the outer layout and places its button in the new size
+        assert_eq!(ui.update_layouts(), Ok(()));
+        assert_eq!(ui.rect(inner), Some((15, 36, 370, 300)));
+        assert_eq!(ui.rect(ok), Some((15, 36, 340, 38)));
+
+        assert!(ui.set_rect(outer, 0, 0, 500, 300));
+        assert_eq!(ui.update_layouts(), Ok(()));
+        assert_eq!(ui.rect(inner), Some((15, 36, 470, 300)));
+        assert_eq!(ui.rect(ok), Some((15, 36, 440, 38)));
+    }
+
+    #[test]
+    fn test_box_layouts() {
+        let mut ui = UI::new(800, 600);
+        let menu = ui.add_widget(Parent::Root, new_form(0, 0, 300, 300, "Menu")).unwrap();
+        let hint = ui.add_widget(Parent::Root, new_label(0, 0, "Hi")).unwrap();
+        assert_eq!(ui.set_box_layout(menu, Layout::Vertical), Ok(()));
+        assert_eq!(ui.layout_kind(menu), Layout::Vertical);
+
+        //Children flow into the layout as they are added
+        let resume = ui.add_widget(menu, new_button(0, 0, 0, 0, "Resume")).unwrap();
+        let quit = ui.add_widget(menu, new_button(0, 0, 0, 0, "Quit")).unwrap();
+        assert_eq!(ui.update_layouts(), Ok(()));
+        assert_eq!(ui.rect(resume), Some((15, 36, 270, 38)));
+        assert_eq!(ui.rect(quit), Some((15, 82, 270, 38)));
+
+        assert!(ui.set_spacing(menu, 0));
+        assert!(ui.set_stretch(quit, 1));
+        assert!(!ui.set_stretch(hint, 1));
+        assert!(!ui.set_spacing(hint, 0));
+        ui.render();
+        assert_eq!(ui.rect(quit), Some((15, 74, 270, 211)));
+
+        //Resizing the form and moving a widget into it places the children again
+        assert!(ui.set_rect(menu, 0, 0, 300, 200));
+        assert_eq!(ui.reparent(hint, menu, 0), Ok(()));
+        assert!(ui.set_item_alignment(hint, Some(layout::Alignment::End)));
+        assert_eq!(ui.update_layouts(), Ok(()));
+        assert_eq!(ui.rect(quit), Some((15, 74, 270, 93)));
+        assert_eq!(ui.rect(hint), Some((267, 167, 18, 18)));
+
+        assert_eq!(ui.set_box_layout(menu, Layout::NoLayout), Ok(()));
+        assert_eq!(ui.layout_kind(menu), Layout::NoLayout);
+        assert!(ui.set_rect(quit, 5, 5, 100, 40));
+        ui.render();
+        assert_eq!(ui.rect(quit), Some((5, 5, 100, 40)));
+    }
+}
@@ -1,10 +1,58 @@
 
 use cassowary;
+use std::cmp;
 use std::cmp::Ordering;
 use std::collections::BTreeMap;
 use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use gui::WidgetHandle;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LayoutError {
+    NoCellToSpan { column: u32, row: u32 },
+    NoCellToAlign { column: u32, row: u32 },
+    SpanOverlapsCell { column: u32, row: u32 },
+    SpanTooLarge { column: u32, row: u32, columns: u32, rows: u32 },
+    InvalidItem(WidgetHandle),
+    Solver(String),
+}
 
-type WidgetHandle = i32;
+impl fmt::Display for LayoutError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LayoutError::NoCellToSpan{column, row} => write!(f, "Need grid cell to span from at ({}, {})", column, row),
+            LayoutError::NoCellToAlign{column, row} => write!(f, "Need grid cell to align at ({}, {})", column, row),
+            LayoutError::SpanOverlapsCell{column, row} => write!(f, "Span overlaps the grid cell at ({}, {})", column, row),
+            LayoutError::SpanTooLarge{column, row, columns, rows} => write!(f, "Span of {}x{} cells at ({}, {}) doesn't fit in the grid", columns, rows, column, row),
+            LayoutError::InvalidItem(item) => write!(f, "Widget {} can't be placed in this layout", item),
+            LayoutError::Solver(ref message) => write!(f, "Layout constraints couldn't be solved: {}", message),
+        }
+    }
+}
+
+impl Error for LayoutError {}
+
+impl From<cassowary::AddConstraintError> for LayoutError {
+    fn from(e: cassowary::AddConstraintError) -> LayoutError {
+        LayoutError::Solver(format!("{:?}", e))
+    }
+}
+
+impl From<cassowary::AddEditVariableError> for LayoutError {
+    fn from(e: cassowary::AddEditVariableError) -> LayoutError {
+        LayoutError::Solver(format!("{:?}", e))
+    }
+}
+
+impl From<cassowary::SuggestValueError> for LayoutError {
+    fn from(e: cassowary::SuggestValueError) -> LayoutError {
+        LayoutError::Solver(format!("{:?}", e))
+    }
+}
+
+//Grids are laid out by hand or from data files, no sane grid needs more columns or rows
+const MAX_GRID_SIZE: u32 = 256;
 
 #[derive(Eq, PartialEq, Clone)]
 struct LayoutPosition(u32, u32);
@@ -54,6 +102,9 @@ struct Component {
     standard_width: u32,
     standard_height: u32,
 
+    horizontal: Alignment,
+    vertical: Alignment,
+
     item: WidgetHandle,
 }
 
@@ -77,6 +128,8 @@ fn new_component(item: WidgetHandle, standard_width: u32, standard_height: u32)
             item: item,
             standard_width: standard_width,
             standard_height: standard_height,
+            horizontal: Alignment::Fill,
+            vertical: Alignment::Fill,
             leading: 0,
             width: 0,
             top: 0,
@@ -123,17 +176,108 @@ impl Cell {
             Cell::Span(ref mut s) => s.height = x
         }
     }
+}
 
-    pub fn get_preferred_height(&self) -> u32 {
-        match *self {
-            Cell::Component(ref c) => c.standard_height,
-            Cell::Span(ref c) => 12
+/***********************************************************************************
+ *      Track
+ *      
+ *      How a column or row of a grid is sized. Tracks with a weight share
+ *      the space left over by the others in proportion to their weights,
+ *      tracks with weight 0 take the standard size of their components.
+ *      min and max limit the size in pixels either way.
+ ***********************************************************************************/
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Track {
+    weight: u32,
+    min: u32,
+    max: u32,
+}
+
+impl Track {
+    fn new(weight: u32) -> Track {
+        Track {
+            weight: weight,
+            min: 0,
+            max: std::u32::MAX,
+        }
+    }
+}
+
+/***********************************************************************************
+ *      solve_tracks
+ *      
+ *      Sizes the columns or the rows of a grid along one axis of length
+ *      total, between the margins at both ends. gaps holds the gap after
+ *      every track but the last. Returns where each track starts and its
+ *      size. If the tracks don't fill the axis the rest is left after the
+ *      last one, if they don't fit they run past its end.
+ ***********************************************************************************/
+fn solve_tracks(total: f64, margins: (f64, f64), gaps: &[f64], tracks: &[Track], preferred: &[f64]) -> Result<(Vec<f64>, Vec<f64>), LayoutError> {
+    use cassowary::{Solver, Variable, Term, Expression};
+    use cassowary::strength::{self, WEAK, MEDIUM, STRONG, REQUIRED};
+    use cassowary::WeightedRelation::*;
+
+    //Limits beat standard sizes, which beat filling the axis, which beats the weights
+    let fill = strength::create(0.0, 0.5, 0.0, 1.0);
+
+    let mut solver = Solver::new();
+    let sizes: Vec<Variable> = tracks.iter().map(|_| Variable::new()).collect();
+    let rest = Variable::new();
+
+    let fixed = gaps.iter().fold(margins.0 + margins.1, |sum, gap| sum + gap);
+    let mut terms: Vec<Term> = sizes.iter().map(|size| new_term(*size, 1.0)).collect();
+    terms.push(new_term(rest, 1.0));
+    solver.add_constraints(&[Expression::new(terms, fixed) |EQ(REQUIRED)| f64::max(total, fixed),
+                             rest |GE(REQUIRED)| 0.0,
+                             rest |EQ(fill)| 0.0])?;
+
+    let mut last_weighted: Option<usize> = None;
+    for (i, track) in tracks.iter().enumerate() {
+        solver.add_constraint(sizes[i] |GE(REQUIRED)| 0.0)?;
+        if track.min > 0 {
+            solver.add_constraint(sizes[i] |GE(STRONG)| track.min as f64)?;
         }
+        if track.max < std::u32::MAX {
+            solver.add_constraint(sizes[i] |LE(STRONG)| track.max as f64)?;
+        }
+
+        if track.weight == 0 {
+            solver.add_constraint(sizes[i] |EQ(MEDIUM)| preferred[i])?;
+            continue;
+        }
+
+        //size[i] / weight[i] == size[j] / weight[j] for all weighted tracks
+        if let Some(j) = last_weighted {
+            solver.add_constraint(sizes[i] * tracks[j].weight as f64 |EQ(WEAK)| sizes[j] * track.weight as f64)?;
+        }
+        last_weighted = Some(i);
+    }
+
+    let mut starts = Vec::new();
+    let mut lengths = Vec::new();
+    let mut position = margins.0;
+    for (i, size) in sizes.iter().enumerate() {
+        let length = solver.get_value(*size);
+        starts.push(position);
+        lengths.push(length);
+        position = position + length + gaps.get(i).cloned().unwrap_or(0.0);
     }
+
+    Ok((starts, lengths))
 }
 
 pub struct GridLayout {
     wrap: u32,
+    width: u32,
+    height: u32,
+    columns: BTreeMap<u32, Track>,
+    rows: BTreeMap<u32, Track>,
+    margins: (u32, u32, u32, u32),
+    horizontal_gap: u32,
+    vertical_gap: u32,
+    column_gaps: BTreeMap<u32, u32>,
+    row_gaps: BTreeMap<u32, u32>,
+    padding: u32,
     current_x: u32,
     current_y: u32,
     grid: BTreeMap<LayoutPosition, Cell>
@@ -143,7 +287,17 @@ pub struct GridLayout {
 enum Action {
     Add{item: WidgetHandle},
     Wrap,
-    Span(u32, u32)
+    Span(u32, u32),
+    Align(Alignment, Alignment),
+    ColumnWeight(u32, u32),
+    RowWeight(u32, u32),
+    ColumnLimits(u32, u32, u32),
+    RowLimits(u32, u32, u32),
+    Margins(u32, u32, u32, u32),
+    Gaps(u32, u32),
+    Padding(u32),
+    ColumnGap(u32, u32),
+    RowGap(u32, u32),
 }
 
 pub struct AccessLayout {
@@ -177,17 +331,88 @@ impl AccessLayout {
         self
     }
 
+    //Where the item is placed in its cell, horizontally and vertically
+    pub fn align(&mut self, horizontal: Alignment, vertical: Alignment) -> &mut AccessLayout {
+        self.actions.push(Action::Align(horizontal, vertical));
+        self
+    }
+
     pub fn wrap(&mut self) -> WrapOnlyAccessLayout {
         self.actions.push(Action::Wrap);
 
         WrapOnlyAccessLayout::new(self)
     }
+
+    //Column and row sizes, see GridLayout::column_weight and GridLayout::column_limits
+    pub fn column_weight(&mut self, column: u32, weight: u32) -> &mut AccessLayout {
+        self.actions.push(Action::ColumnWeight(column, weight));
+        self
+    }
+
+    pub fn row_weight(&mut self, row: u32, weight: u32) -> &mut AccessLayout {
+        self.actions.push(Action::RowWeight(row, weight));
+        self
+    }
+
+    pub fn column_limits(&mut self, column: u32, min: u32, max: u32) -> &mut AccessLayout {
+        self.actions.push(Action::ColumnLimits(column, min, max));
+        self
+    }
+
+    pub fn row_limits(&mut self, row: u32, min: u32, max: u32) -> &mut AccessLayout {
+        self.actions.push(Action::RowLimits(row, min, max));
+        self
+    }
+
+    pub fn column_width(&mut self, column: u32, width: u32) -> &mut AccessLayout {
+        self.column_limits(column, width, width)
+    }
+
+    pub fn row_height(&mut self, row: u32, height: u32) -> &mut AccessLayout {
+        self.row_limits(row, height, height)
+    }
+
+    //Spacing of the grid, see GridLayout::margins and GridLayout::column_gap
+    pub fn margins(&mut self, left: u32, top: u32, right: u32, bottom: u32) -> &mut AccessLayout {
+        self.actions.push(Action::Margins(left, top, right, bottom));
+        self
+    }
+
+    pub fn gaps(&mut self, horizontal: u32, vertical: u32) -> &mut AccessLayout {
+        self.actions.push(Action::Gaps(horizontal, vertical));
+        self
+    }
+
+    pub fn padding(&mut self, padding: u32) -> &mut AccessLayout {
+        self.actions.push(Action::Padding(padding));
+        self
+    }
+
+    pub fn column_gap(&mut self, column: u32, gap: u32) -> &mut AccessLayout {
+        self.actions.push(Action::ColumnGap(column, gap));
+        self
+    }
+
+    pub fn row_gap(&mut self, row: u32, gap: u32) -> &mut AccessLayout {
+        self.actions.push(Action::RowGap(row, gap));
+        self
+    }
 }
 
 impl GridLayout {
     pub fn new() -> GridLayout {
         GridLayout {
             wrap: 0,
+            width: 300,
+            height: 300,
+            columns: BTreeMap::new(),
+            rows: BTreeMap::new(),
+            margins: (12, 12, 12, 12),
+            horizontal_gap: 8,
+            vertical_gap: 8,
+            column_gaps: BTreeMap::new(),
+            row_gaps: BTreeMap::new(),
+            padding: 0,
             current_x: 0,
             current_y: 0,
             grid: BTreeMap::new()
@@ -199,8 +424,121 @@ impl GridLayout {
 
         self
     }
+
+    //Size of the area the grid is placed in, including its margins
+    pub fn set_size(mut self, width: u32, height: u32) -> GridLayout {
+        self.width = width;
+        self.height = height;
+
+        self
+    }
+
+/***********************************************************************************
+ *      GridLayout::column_weight
+ *      GridLayout::row_weight
+ *      
+ *      Columns and rows with a weight share the space the others leave over
+ *      in proportion to their weights. Weight 0 keeps the standard size of
+ *      the largest component. Columns have weight 1 and rows weight 0 until
+ *      set otherwise.
+ ***********************************************************************************/
+    pub fn column_weight(&mut self, column: u32, weight: u32) -> &mut GridLayout {
+        let mut track = self.column_track(column);
+        track.weight = weight;
+        self.columns.insert(column, track);
+        self
+    }
+
+    pub fn row_weight(&mut self, row: u32, weight: u32) -> &mut GridLayout {
+        let mut track = self.row_track(row);
+        track.weight = weight;
+        self.rows.insert(row, track);
+        self
+    }
+
+/***********************************************************************************
+ *      GridLayout::column_limits
+ *      GridLayout::row_limits
+ *      GridLayout::column_width
+ *      GridLayout::row_height
+ *      
+ *      Keeps a column or row between min and max pixels, std::u32::MAX for
+ *      no maximum. column_width and row_height fix the size.
+ ***********************************************************************************/
+    pub fn column_limits(&mut self, column: u32, min: u32, max: u32) -> &mut GridLayout {
+        let mut track = self.column_track(column);
+        track.min = min;
+        track.max = max;
+        self.columns.insert(column, track);
+        self
+    }
+
+    pub fn row_limits(&mut self, row: u32, min: u32, max: u32) -> &mut GridLayout {
+        let mut track = self.row_track(row);
+        track.min = min;
+        track.max = max;
+        self.rows.insert(row, track);
+        self
+    }
+
+    pub fn column_width(&mut self, column: u32, width: u32) -> &mut GridLayout {
+        self.column_limits(column, width, width)
+    }
+
+    pub fn row_height(&mut self, row: u32, height: u32) -> &mut GridLayout {
+        self.row_limits(row, height, height)
+    }
+
+/***********************************************************************************
+ *      GridLayout::margins
+ *      GridLayout::gaps
+ *      GridLayout::padding
+ *      
+ *      Space around the grid at each edge, between neighbouring columns and
+ *      rows, and inside every cell around its item. The defaults are 12
+ *      pixels of margin, 8 pixel gaps and no padding.
+ ***********************************************************************************/
+    pub fn margins(&mut self, left: u32, top: u32, right: u32, bottom: u32) -> &mut GridLayout {
+        self.margins = (left, top, right, bottom);
+        self
+    }
+
+    pub fn gaps(&mut self, horizontal: u32, vertical: u32) -> &mut GridLayout {
+        self.horizontal_gap = horizontal;
+        self.vertical_gap = vertical;
+        self
+    }
+
+    pub fn padding(&mut self, padding: u32) -> &mut GridLayout {
+        self.padding = padding;
+        self
+    }
+
+/***********************************************************************************
+ *      GridLayout::column_gap
+ *      GridLayout::row_gap
+ *      
+ *      Overrides the gap after a column or a row, to group the columns and
+ *      rows in front of it apart from the ones behind it.
+ ***********************************************************************************/
+    pub fn column_gap(&mut self, column: u32, gap: u32) -> &mut GridLayout {
+        self.column_gaps.insert(column, gap);
+        self
+    }
+
+    pub fn row_gap(&mut self, row: u32, gap: u32) -> &mut GridLayout {
+        self.row_gaps.insert(row, gap);
+        self
+    }
     
-    pub fn access(&mut self, access_closure: &Fn(&mut AccessLayout), standard_size_closure: &Fn(WidgetHandle) -> (u32, u32)) {
+/***********************************************************************************
+ *      GridLayout::access
+ *      
+ *      Builds the grid from the actions recorded by the access closure and
+ *      solves it. The standard size closure returns None for items that
+ *      can't be placed in the layout.
+ ***********************************************************************************/
+    pub fn access(&mut self, access_closure: &Fn(&mut AccessLayout), standard_size_closure: &Fn(WidgetHandle) -> Option<(u32, u32)>) -> Result<(), LayoutError> {
         let mut access_object = AccessLayout { actions: Vec::new() };  
 
         access_closure(&mut access_object);
@@ -208,15 +546,72 @@ impl GridLayout {
         for a in access_object.actions {
             match a {
                 Action::Add{item} => { 
-                    let (width, height) = standard_size_closure(item);
+                    let (width, height) = match standard_size_closure(item) {
+                        Some(size) => size,
+                        None => return Err(LayoutError::InvalidItem(item))
+                    };
                     self.add(item, width, height);
                 },
                 Action::Wrap => {self.current_x = 0; self.current_y = self.current_y + 1;},
-                Action::Span(x, y) => self.span(x, y)
+                Action::Span(x, y) => self.span(x, y)?,
+                Action::Align(horizontal, vertical) => self.align(horizontal, vertical)?,
+                Action::ColumnWeight(column, weight) => {self.column_weight(column, weight);},
+                Action::RowWeight(row, weight) => {self.row_weight(row, weight);},
+                Action::ColumnLimits(column, min, max) => {self.column_limits(column, min, max);},
+                Action::RowLimits(row, min, max) => {self.row_limits(row, min, max);},
+                Action::Margins(left, top, right, bottom) => {self.margins(left, top, right, bottom);},
+                Action::Gaps(horizontal, vertical) => {self.gaps(horizontal, vertical);},
+                Action::Padding(padding) => {self.padding(padding);},
+                Action::ColumnGap(column, gap) => {self.column_gap(column, gap);},
+                Action::RowGap(row, gap) => {self.row_gap(row, gap);},
+            }
+        }
+
+        self.internal_update()
+    }
+
+/***********************************************************************************
+ *      GridLayout::relayout
+ *      
+ *      Solves the grid again in an area of the given size with fresh
+ *      standard sizes, for example after the text of a label changed or
+ *      the parent was resized. Items the closure returns None for are taken
+ *      out of the grid together with their spans.
+ ***********************************************************************************/
+    pub fn relayout(&mut self, size: (u32, u32), standard_size_closure: &Fn(WidgetHandle) -> Option<(u32, u32)>) -> Result<(), LayoutError> {
+        self.width = size.0;
+        self.height = size.1;
+        let mut removed: Vec<LayoutPosition> = Vec::new();
+
+        for (position, cell) in self.grid.iter_mut() {
+            match *cell {
+                Cell::Component(ref mut c) => match standard_size_closure(c.item) {
+                    Some((width, height)) => {
+                        c.standard_width = width;
+                        c.standard_height = height;
+                    }
+                    None => removed.push(position.clone())
+                },
+                Cell::Span(_) => ()
+            }
+        }
+
+        for position in removed {
+            self.grid.remove(&position);
+
+            let spans: Vec<LayoutPosition> = self.grid.iter()
+                .filter(|&(_, cell)| match *cell {
+                    Cell::Span(ref span) => span.start_position == position,
+                    Cell::Component(_) => false
+                })
+                .map(|(p, _)| p.clone())
+                .collect();
+            for p in spans {
+                self.grid.remove(&p);
             }
         }
 
-        self.internal_update();        
+        self.internal_update()
     }
 
     pub fn update(&self, result_closure: &mut FnMut(WidgetHandle, (u32, u32, u32, u32))) {
@@ -245,240 +640,165 @@ impl GridLayout {
         self.grid.insert(LayoutPosition::new(self.current_x, self.current_y), new_component(item, standard_width, standard_height));
     }
 
-    fn span(&mut self, x: u32, y: u32) {
+    fn align(&mut self, horizontal: Alignment, vertical: Alignment) -> Result<(), LayoutError> {
+        match self.grid.get_mut(&LayoutPosition::new(self.current_x, self.current_y)) {
+            Some(&mut Cell::Component(ref mut c)) => {
+                c.horizontal = horizontal;
+                c.vertical = vertical;
+                Ok(())
+            }
+            _ => Err(LayoutError::NoCellToAlign{column: self.current_x, row: self.current_y})
+        }
+    }
+
+    fn span(&mut self, x: u32, y: u32) -> Result<(), LayoutError> {
         if !self.grid.contains_key( &LayoutPosition::new(self.current_x, self.current_y) ) {
-            panic!("Need grid cell to span from.");
+            return Err(LayoutError::NoCellToSpan{column: self.current_x, row: self.current_y});
+        }
+
+        //Check the size before inserting anything, a huge span would fill the whole memory
+        let end_x = self.current_x.checked_add(x);
+        let end_y = self.current_y.checked_add(y);
+        match (end_x, end_y) {
+            (Some(end_x), Some(end_y)) if end_x <= MAX_GRID_SIZE && end_y <= MAX_GRID_SIZE => (),
+            _ => return Err(LayoutError::SpanTooLarge{column: self.current_x, row: self.current_y, columns: x, rows: y})
         }
 
         for cell_y in 0..y {
             for cell_x in 0..x {
                 if cell_x == 0 && cell_y == 0 {continue;}
 
-                self.grid.insert(LayoutPosition::new(self.current_x + cell_x, self.current_y + cell_y), new_span(x, y));
+                let position = LayoutPosition::new(self.current_x + cell_x, self.current_y + cell_y);
+                if self.grid.contains_key(&position) {
+                    return Err(LayoutError::SpanOverlapsCell{column: position.0, row: position.1});
+                }
+
+                self.grid.insert(position, new_span(self.current_x, self.current_y));
             }
         }
+
+        Ok(())
     }
 
-    fn internal_update(&mut self) {
+    fn internal_update(&mut self) -> Result<(), LayoutError> {
         
-        let mut num_columns = 0usize;
-
-        if self.wrap == 0 {
-            //Find the longest row since this grid doesn't have a fixed wrap
-            let mut current_row: i32 = -1;
-            let mut num_in_row = 1usize;
-            for (ref position, _) in &self.grid {
-                if position.1 as i32 > current_row {
-                    //We have reached a new row
-                    if num_in_row > num_columns { num_columns = num_in_row; } //We have found a new longest row
-                    num_in_row = 0;
-                    current_row = position.1 as i32;
-                }
-                num_in_row = num_in_row + 1;
-            }
-            if num_in_row > num_columns { num_columns = num_in_row; } //Last row was the longest row
-        }
-        else {
-            num_columns = self.wrap as usize;
+        //Count columns up to the rightmost cell, a row may have holes left by removed items
+        let mut num_columns = self.wrap as usize;
+        for position in self.grid.keys() {
+            num_columns = cmp::max(num_columns, position.0 as usize + 1);
         }
 
         //Find the number of rows
-        let position: LayoutPosition;
-        { 
-            position = self.grid.iter().next_back().unwrap().0.clone();
-        }
-        let num_rows = position.1 as usize + 1;
-
-        self.calculate_row(num_rows, num_columns);
-    }
-
-    fn calculate_row(&mut self, num_rows: usize, num_columns: usize) {
-        use cassowary::{Solver, Variable, Term, Expression};
-        use cassowary::strength::{WEAK, MEDIUM, STRONG, REQUIRED};
-        use cassowary::WeightedRelation::*;
-
-        let mut var_names: HashMap<Variable, (String, f64) > = HashMap::new();
-
-        let mut solver = Solver::new();
-
-        let widget_width: Variable = Variable::new();
-        let widget_height: Variable = Variable::new();
-
-        //One vector for every row in the grid
-        let mut width_vars: Vec<Vec<Variable>> = Vec::new();
-        let mut height_vars: Vec<Vec<Variable>> = Vec::new();
-        let mut trailing_gap_vars: Vec<Vec<Variable>> = Vec::new();
-        let mut inferior_gap_vars: Vec<Vec<Variable>> = Vec::new();
-        let mut top_margin: Vec<Variable> = Vec::new();
-        let mut bottom_margin: Vec<Variable> = Vec::new();
-        let mut left_margin: Vec<Variable> = Vec::new();
-        let mut right_margin: Vec<Variable> = Vec::new();
-        let mut all_vars_equal_parent_width_expr: Vec<Expression> = Vec::new();
-        let mut all_vars_equal_parent_height: Vec<Vec<Term>> = Vec::new();
-        let mut all_vars_equal_parent_height_expr: Vec<Expression> = Vec::new();
-
-        for c in 0..num_columns {
-            all_vars_equal_parent_height.push(Vec::new());
-            top_margin.push(Variable::new());
-            bottom_margin.push(Variable::new());
-            solver.add_edit_variable(bottom_margin[c], 1.0).unwrap();
-            solver.suggest_value(bottom_margin[c], 1000000.0).unwrap();
-            var_names.insert(top_margin[c].clone(), ("Top Margin".to_owned(), 0.0));
-            var_names.insert(bottom_margin[c].clone(), ("Bottom Margin".to_owned(), 0.0));
-
-            all_vars_equal_parent_height[c].push(new_term(top_margin[c], 1.0));
-            all_vars_equal_parent_height[c].push(new_term(bottom_margin[c], 1.0));
-        }
-
-
-        var_names.insert(widget_width.clone(), ("Widget width".to_owned(), 0.0));
-        var_names.insert(widget_height.clone(), ("Widget height".to_owned(), 0.0));
-
-        for row in 0..num_rows {
-            width_vars.push(Vec::new());
-            height_vars.push(Vec::new());
-            trailing_gap_vars.push(Vec::new());
-            inferior_gap_vars.push(Vec::new());
-            left_margin.push(Variable::new());
-            right_margin.push(Variable::new());
-           
-            var_names.insert(left_margin[row].clone(), ("Left Margin".to_owned(), 0.0));
-            var_names.insert(right_margin[row].clone(), ("Right Margin".to_owned(), 0.0));
-            
-            let mut all_vars_equal_parent_width: Vec<Term> = Vec::new();
-            all_vars_equal_parent_width.push(new_term(left_margin[row], 1.0));
-            all_vars_equal_parent_width.push(new_term(right_margin[row], 1.0));
-
-            for c in 0usize..num_columns {
-                width_vars[row].push(Variable::new());
-                var_names.insert(width_vars[row][c].clone(), (format!("w{}", c), 0.0));
-                all_vars_equal_parent_width.push(new_term(width_vars[row][c], 1.0));
-
-                let mut height = 0.0;
-                match self.grid.get(&LayoutPosition::new(c as u32, row as u32)) {
-                    Some(ref cell) => {
-                        height = cell.get_preferred_height() as f64;
-                    }
-                    None =>()
-                }
+        let num_rows = match self.grid.keys().next_back() {
+            Some(position) => position.1 as usize + 1,
+            None => return Ok(()) //Nothing to lay out
+        };
 
-                height_vars[row].push(Variable::new());
-                var_names.insert(height_vars[row][c], (format!("h{}", c), 0.0));
-                all_vars_equal_parent_height[c].push(new_term(height_vars[row][c], 1.0));
-                solver.add_edit_variable(height_vars[row][c], 1.0).unwrap();
-                solver.suggest_value(height_vars[row][c], height).unwrap();
-                if row + 1 != num_rows {
-                    inferior_gap_vars[row].push(Variable::new());
-                    var_names.insert(inferior_gap_vars[row][c], (format!("i{}", c), 0.0));
-                    all_vars_equal_parent_height[c].push(new_term(inferior_gap_vars[row][c], 1.0));
-                }
+        self.calculate_grid(num_rows, num_columns)
+    }
 
-                if c + 1 != num_columns {
-                    trailing_gap_vars[row].push(Variable::new());
-                    var_names.insert(trailing_gap_vars[row][c], (format!("g{}", c), 0.0));
-                    all_vars_equal_parent_width.push(new_term(trailing_gap_vars[row][c], 1.0));
-                    solver.add_edit_variable(trailing_gap_vars[row][c], 1.0).unwrap();
-                    solver.suggest_value(trailing_gap_vars[row][c], 8.0).unwrap();
-                }
+    //Number of columns and rows covered by every component that has spans
+    fn component_extents(&self) -> HashMap<(u32, u32), (u32, u32)> {
+        let mut extents: HashMap<(u32, u32), (u32, u32)> = HashMap::new();
+
+        for (position, cell) in &self.grid {
+            if let Cell::Span(ref span) = *cell {
+                let start = (span.start_position.0, span.start_position.1);
+                let extent = extents.entry(start).or_insert((1, 1));
+                extent.0 = cmp::max(extent.0, position.0 - start.0 + 1);
+                extent.1 = cmp::max(extent.1, position.1 - start.1 + 1);
             }
+        }
 
-            all_vars_equal_parent_width_expr.push(Expression::new(all_vars_equal_parent_width, 0.0));
+        extents
+    }
 
-            solver.add_constraints(&[all_vars_equal_parent_width_expr[row].clone() |EQ(REQUIRED)| widget_width]).unwrap();
-            solver.add_constraints(&[left_margin[row]    |LE(STRONG)|    12.0, 
-                                     right_margin[row]   |LE(STRONG)|    12.0,] ).unwrap();
-        
-            if num_columns > 1 {
-                for i in 0 .. num_columns - 1 {
-                    solver.add_constraint(width_vars[row][i] |EQ(STRONG)| width_vars[row][i+1]).unwrap()
+    fn calculate_grid(&mut self, num_rows: usize, num_columns: usize) -> Result<(), LayoutError> {
+        let extents = self.component_extents();
+
+        //Columns and rows without a weight of their own take the standard size of
+        //their largest component, spanning components are left out
+        let mut preferred_widths = vec![0.0; num_columns];
+        let mut preferred_heights = vec![0.0; num_rows];
+        let padding = 2.0 * self.padding as f64;
+        for (position, cell) in &self.grid {
+            if let Cell::Component(ref c) = *cell {
+                let extent = extents.get(&(position.0, position.1)).cloned().unwrap_or((1, 1));
+                let (column, row) = (position.0 as usize, position.1 as usize);
+                if extent.0 == 1 && column < num_columns {
+                    preferred_widths[column] = f64::max(preferred_widths[column], c.standard_width as f64 + padding);
                 }
-                for i in 0 .. num_columns - 2 {
-                    solver.add_constraints(&[trailing_gap_vars[row][i] |EQ(STRONG)| trailing_gap_vars[row][i+1],
-                                            trailing_gap_vars[row][i] |LE(STRONG)| 8.0]).unwrap()
+                if extent.1 == 1 && row < num_rows {
+                    preferred_heights[row] = f64::max(preferred_heights[row], c.standard_height as f64 + padding);
                 }
             }
         }
 
-        for c in 0..num_columns {
-            all_vars_equal_parent_height_expr.push(Expression::new(all_vars_equal_parent_height[c].clone(), 0.0));
-            solver.add_constraint(all_vars_equal_parent_height_expr[c].clone() |EQ(REQUIRED)| widget_height).unwrap();
-            solver.add_constraints(&[top_margin[c]      |EQ(STRONG)|    12.0,
-                                     bottom_margin[c]   |GE(STRONG)|     12.0,
-            ] ).unwrap();
-
-            if num_rows > 1 {
-                for row in 0..num_rows - 1 {
-                    if c < num_columns - 1 {
-                        let num_height_vars = height_vars.len();
-                        let num_inferior_gap_vars = inferior_gap_vars.len();
-                        let num_height_var_row = height_vars[row].len();
-                        let num_inferior_hap_var_row = inferior_gap_vars[row].len();
-                        solver.add_constraint(height_vars[row][c] + inferior_gap_vars[row][c] |EQ(MEDIUM)| height_vars[row][c+1] + inferior_gap_vars[row][c+1]).unwrap();
-                    }
-                    solver.add_constraint(inferior_gap_vars[row][c] |GE(WEAK)| 8.0).unwrap();
-                    if row < num_rows - 2 {
-                        solver.add_constraint(inferior_gap_vars[row][c] |EQ(WEAK)| inferior_gap_vars[row+1][c]);
-                    }
-                }   
-            }
+        let columns: Vec<Track> = (0..num_columns).map(|c| self.column_track(c as u32)).collect();
+        let rows: Vec<Track> = (0..num_rows).map(|r| self.row_track(r as u32)).collect();
+        let column_gaps: Vec<f64> = (1..num_columns).map(|c| self.column_gap_after(c as u32 - 1) as f64).collect();
+        let row_gaps: Vec<f64> = (1..num_rows).map(|r| self.row_gap_after(r as u32 - 1) as f64).collect();
+        let (left, top, right, bottom) = self.margins;
+        let (xs, widths) = solve_tracks(self.width as f64, (left as f64, right as f64), &column_gaps, &columns, &preferred_widths)?;
+        let (ys, heights) = solve_tracks(self.height as f64, (top as f64, bottom as f64), &row_gaps, &rows, &preferred_heights)?;
+
+        for (position, cell) in self.grid.iter_mut() {
+            let (column, row) = (position.0 as usize, position.1 as usize);
+            if column >= num_columns {continue;}
+
+            let extent = extents.get(&(position.0, position.1)).cloned().unwrap_or((1, 1));
+            let last_column = cmp::min(column + extent.0 as usize, num_columns) - 1;
+            let last_row = cmp::min(row + extent.1 as usize, num_rows) - 1;
+
+            let x = xs[column].round() as u32;
+            let y = ys[row].round() as u32;
+            let width = (xs[last_column] + widths[last_column]).round() as u32 - x;
+            let height = (ys[last_row] + heights[last_row]).round() as u32 - y;
+
+            //Components that don't fill their cell keep their standard size
+            let padding = self.padding;
+            let (x, width) = (x + cmp::min(padding, width / 2), width.saturating_sub(padding.saturating_mul(2)));
+            let (y, height) = (y + cmp::min(padding, height / 2), height.saturating_sub(padding.saturating_mul(2)));
+            let ((x, width), (y, height)) = match *cell {
+                Cell::Component(ref c) => (place(c.horizontal, x, width, c.standard_width),
+                                           place(c.vertical, y, height, c.standard_height)),
+                Cell::Span(_) => ((x, width), (y, height))
+            };
+
+            cell.set_leading(x);
+            cell.set_top(y);
+            cell.set_width(width);
+            cell.set_height(height);
         }
 
+        Ok(())
+    }
 
-        solver.add_edit_variable(widget_width, 1.0).unwrap();
-        solver.add_edit_variable(widget_height, 1.0).unwrap();
-        solver.suggest_value(widget_width, 300.0).unwrap();
-        solver.suggest_value(widget_height, 300.0).unwrap();
-
-        macro_rules! get_value {
-            ($x:expr) => { 
-                match var_names.get($x) {
-                    Some(c) => (c.0.clone(), c.1),
-                    None => ("".to_owned(), 0.0)
-                }
-            }
+    fn column_gap_after(&self, column: u32) -> u32 {
+        match self.column_gaps.get(&column) {
+            Some(gap) => *gap,
+            None => self.horizontal_gap
         }
+    }
 
-        macro_rules! set_value{
-            ($var:ident, $value:ident) => {
-                match var_names.get_mut($var) {
-                    Some(ref mut c) => (*c).1 = $value,
-                    None => ()
-                }
-            }
+    fn row_gap_after(&self, row: u32) -> u32 {
+        match self.row_gaps.get(&row) {
+            Some(gap) => *gap,
+            None => self.vertical_gap
         }
-        for c in solver.fetch_changes().iter() {
-            let (ref var, value) = *c;
-            set_value!(var, value);
-        }        
-
-        let mut y: Vec<f64> = Vec::new();
-        for row in 0..num_rows {
-            let mut x = get_value!(&left_margin[row]).1;
-            for i in 0..num_columns{
-                if row == 0 { y.push(get_value!(&top_margin[i]).1)}
-
-                match self.grid.get_mut(&LayoutPosition::new(i as u32, row as u32)) {
-                    Some( mut c) => {
-                        (*c).set_leading(x as u32);
-                        let width = get_value!(&width_vars[row][i]).1;
-                        let height = get_value!(&height_vars[row][i]).1;
-                        (*c).set_top(y[i] as u32);
-                        (*c).set_width(width as u32);
-                        x = x + width;
-                        y[i] = y[i] + height;
-                        (*c).set_height(height as u32);
-                    },
-                    None =>  x = x + get_value!(&width_vars[row][i]).1
-                }
+    }
 
-                if i < num_columns - 1 {
-                    x = x + get_value!(&trailing_gap_vars[row][i]).1;
-                }
+    fn column_track(&self, column: u32) -> Track {
+        match self.columns.get(&column) {
+            Some(track) => *track,
+            None => Track::new(1) //Columns share the width equally by default
+        }
+    }
 
-                if row < num_rows - 1 {
-                    y[i] = y[i] + get_value!(&inferior_gap_vars[row][i]).1;
-                }
-            }
+    fn row_track(&self, row: u32) -> Track {
+        match self.rows.get(&row) {
+            Some(track) => *track,
+            None => Track::new(0) //Rows keep their standard height by default
         }
     }
 
@@ -500,9 +820,238 @@ impl GridLayout {
     }
 }
 
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Direction {
+    Vertical,
+    Horizontal
+}
+
+//Where an item is placed in a grid cell or across a BoxLayout
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Alignment {
+    Start,
+    Center,
+    End,
+    Fill
+}
+
+//Start and size of an item with the given standard size in space pixels starting at start
+fn place(alignment: Alignment, start: u32, space: u32, standard: u32) -> (u32, u32) {
+    let size = cmp::min(standard, space);
+    match alignment {
+        Alignment::Start => (start, size),
+        Alignment::Center => (start + (space - size) / 2, size),
+        Alignment::End => (start + space - size, size),
+        Alignment::Fill => (start, space),
+    }
+}
+
+struct BoxItem {
+    item: WidgetHandle,
+    stretch: u32,
+    alignment: Option<Alignment>,
+
+    standard_width: u32,
+    standard_height: u32,
+
+    leading: u32,
+    top: u32,
+    width: u32,
+    height: u32,
+}
+
+/***********************************************************************************
+ *      BoxLayout
+ *      
+ *      Places items one after another, from top to bottom or from left to
+ *      right, spacing pixels apart. Items get their standard size along the
+ *      direction of the layout. Space left over is shared by the items with
+ *      a stretch factor in proportion to it. Across the direction the items
+ *      are aligned inside the layout, filling it by default.
+ ***********************************************************************************/
+pub struct BoxLayout {
+    direction: Direction,
+    spacing: u32,
+    margin: u32,
+    alignment: Alignment,
+    items: Vec<BoxItem>,
+}
+
+impl BoxLayout {
+    pub fn new(direction: Direction) -> BoxLayout {
+        BoxLayout {
+            direction: direction,
+            spacing: 8,
+            margin: 12,
+            alignment: Alignment::Fill,
+            items: Vec::new(),
+        }
+    }
+
+    pub fn direction(&self) -> Direction {
+        self.direction
+    }
+
+    pub fn set_spacing(&mut self, spacing: u32) {
+        self.spacing = spacing;
+    }
+
+    pub fn set_alignment(&mut self, alignment: Alignment) {
+        self.alignment = alignment;
+    }
+
+    pub fn contains(&self, item: WidgetHandle) -> bool {
+        self.items.iter().any(|i| i.item == item)
+    }
+
+    pub fn add(&mut self, item: WidgetHandle) {
+        if self.contains(item) {return;}
+
+        self.items.push(BoxItem {
+            item: item,
+            stretch: 0,
+            alignment: None,
+            standard_width: 0,
+            standard_height: 0,
+            leading: 0,
+            top: 0,
+            width: 0,
+            height: 0,
+        });
+    }
+
+/***********************************************************************************
+ *      BoxLayout::set_stretch
+ *      BoxLayout::set_item_alignment
+ *      
+ *      Settings of a single item. An item without an alignment of its own
+ *      uses the alignment of the layout. Returns false if the item isn't
+ *      in the layout.
+ ***********************************************************************************/
+    pub fn set_stretch(&mut self, item: WidgetHandle, stretch: u32) -> bool {
+        match self.items.iter_mut().find(|i| i.item == item) {
+            Some(i) => {i.stretch = stretch; true},
+            None => false
+        }
+    }
+
+    pub fn set_item_alignment(&mut self, item: WidgetHandle, alignment: Option<Alignment>) -> bool {
+        match self.items.iter_mut().find(|i| i.item == item) {
+            Some(i) => {i.alignment = alignment; true},
+            None => false
+        }
+    }
+
+/***********************************************************************************
+ *      BoxLayout::relayout
+ *      
+ *      Places the items inside a parent of the given size, with fresh
+ *      standard sizes. Items the closure returns None for are taken out of
+ *      the layout. Items that don't fit run past the end of the layout.
+ ***********************************************************************************/
+    pub fn relayout(&mut self, size: (u32, u32), standard_size_closure: &Fn(WidgetHandle) -> Option<(u32, u32)>) {
+        let mut standard_sizes = Vec::new();
+        self.items.retain(|i| match standard_size_closure(i.item) {
+            Some(standard_size) => {standard_sizes.push(standard_size); true},
+            None => false
+        });
+        for (i, (width, height)) in self.items.iter_mut().zip(standard_sizes) {
+            i.standard_width = width;
+            i.standard_height = height;
+        }
+
+        //Work along the main axis and across it, then map back to x and y
+        let vertical = self.direction == Direction::Vertical;
+        let (length, thickness) = if vertical {(size.1, size.0)} else {(size.0, size.1)};
+        let length = length.saturating_sub(2 * self.margin);
+        let thickness = thickness.saturating_sub(2 * self.margin);
+
+        let gaps = self.spacing * (cmp::max(self.items.len(), 1) as u32 - 1);
+        let used = self.items.iter()
+            .map(|i| if vertical {i.standard_height} else {i.standard_width})
+            .fold(gaps, |sum, l| sum + l);
+        let left_over = length.saturating_sub(used);
+        let total_stretch = self.items.iter().fold(0, |sum, i| sum + i.stretch);
+
+        let mut position = self.margin;
+        let mut shared = 0;
+        let mut stretched = 0;
+        for i in self.items.iter_mut() {
+            let (standard_length, standard_thickness) = if vertical {
+                (i.standard_height, i.standard_width)
+            } else {
+                (i.standard_width, i.standard_height)
+            };
+
+            //The last stretched item takes what rounding leaves over
+            let mut extra = 0;
+            if i.stretch > 0 {
+                stretched = stretched + i.stretch;
+                extra = if stretched == total_stretch {
+                    left_over - shared
+                } else {
+                    left_over * i.stretch / total_stretch
+                };
+                shared = shared + extra;
+            }
+            let item_length = standard_length + extra;
+
+            let (across, item_thickness) = place(i.alignment.unwrap_or(self.alignment), self.margin, thickness, standard_thickness);
+
+            if vertical {
+                i.leading = across;
+                i.top = position;
+                i.width = item_thickness;
+                i.height = item_length;
+            }
+            else {
+                i.leading = position;
+                i.top = across;
+                i.width = item_length;
+                i.height = item_thickness;
+            }
+
+            position = position + item_length + self.spacing;
+        }
+    }
+
+    pub fn update(&self, result_closure: &mut FnMut(WidgetHandle, (u32, u32, u32, u32))) {
+        for i in &self.items {
+            result_closure(i.item, (i.leading, i.top, i.width, i.height));
+        }
+    }
+}
+
+/***********************************************************************************
+ *      ChildLayout
+ *      
+ *      The layout that places the children of a widget.
+ ***********************************************************************************/
+pub enum ChildLayout {
+    Grid(GridLayout),
+    Box(BoxLayout),
+}
+
+impl ChildLayout {
+    pub fn relayout(&mut self, size: (u32, u32), standard_size_closure: &Fn(WidgetHandle) -> Option<(u32, u32)>) -> Result<(), LayoutError> {
+        match *self {
+            ChildLayout::Grid(ref mut grid) => grid.relayout(size, standard_size_closure),
+            ChildLayout::Box(ref mut b) => {b.relayout(size, standard_size_closure); Ok(())}
+        }
+    }
+
+    pub fn update(&self, result_closure: &mut FnMut(WidgetHandle, (u32, u32, u32, u32))) {
+        match *self {
+            ChildLayout::Grid(ref grid) => grid.update(result_closure),
+            ChildLayout::Box(ref b) => b.update(result_closure)
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use gui::WidgetId;
 
     #[test]
     fn test_basic_grid_layout() {
@@ -519,7 +1068,7 @@ mod tests {
                         .set_wrap(4);
 
 
-        let w: Vec<i32> = (0..10).collect();
+        let w: Vec<WidgetHandle> = (0..10).map(WidgetId::from_index).collect();
 
         layouter.access(&|ref mut l| {
             l.add(w[0]);
@@ -532,10 +1081,10 @@ mod tests {
             l.add(w[7]).add(w[8]).add(w[9]);
             },
 
-            &|l| -> (u32, u32) {
-                (30, 12)
+            &|l| -> Option<(u32, u32)> {
+                Some((30, 12))
             }
-        );
+        ).unwrap();
 
         layouter.update(&mut |index: WidgetHandle, rect: (u32, u32, u32, u32)| {
             println!("{}: ({}, {}, {}, {})", index, rect.0, rect.1, rect.2, rect.3);
@@ -545,4 +1094,182 @@ mod tests {
 
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn test_grid_layout_errors() {
+        let w: Vec<WidgetHandle> = (0..3).map(WidgetId::from_index).collect();
+
+        let mut layouter = GridLayout::new();
+        let result = layouter.access(&|ref mut l| { l.span(2, 1); }, &|_| Some((30, 12)));
+        assert_eq!(result, Err(LayoutError::NoCellToSpan{column: 0, row: 0}));
+
+        let mut layouter = GridLayout::new();
+        let result = layouter.access(&|ref mut l| { l.add(w[0]).add(w[1]).span(1, 2).wrap(); l.add(w[2]).span(2, 1); },
+                                     &|_| Some((30, 12)));
+        assert_eq!(result, Err(LayoutError::SpanOverlapsCell{column: 1, row: 1}));
+
+        let mut layouter = GridLayout::new();
+        let result = layouter.access(&|ref mut l| { l.add(w[0]).add(w[1]).span(::std::u32::MAX, 1); }, &|_| Some((30, 12)));
+        assert_eq!(result, Err(LayoutError::SpanTooLarge{column: 1, row: 0, columns: ::std::u32::MAX, rows: 1}));
+        let result = layouter.access(&|ref mut l| { l.add(w[2]).span(1, MAX_GRID_SIZE + 1); }, &|_| Some((30, 12)));
+        assert_eq!(result, Err(LayoutError::SpanTooLarge{column: 2, row: 0, columns: 1, rows: MAX_GRID_SIZE + 1}));
+
+        let mut layouter = GridLayout::new();
+        let result = layouter.access(&|ref mut l| { l.add(w[0]).add(w[1]); },
+                                     &|l| if l == w[1] { None } else { Some((30, 12)) });
+        assert_eq!(result, Err(LayoutError::InvalidItem(w[1])));
+
+        //An empty layout has nothing to solve
+        let mut layouter = GridLayout::new();
+        assert_eq!(layouter.access(&|_| (), &|_| Some((30, 12))), Ok(()));
+    }
+
+    #[test]
+    fn test_grid_relayout_after_removal() {
+        let w: Vec<WidgetHandle> = (0..3).map(WidgetId::from_index).collect();
+
+        let mut layouter = GridLayout::new().set_size(300, 100);
+        layouter.access(&|ref mut l| { l.add(w[0]).add(w[1]).add(w[2]); }, &|_| Some((30, 12))).unwrap();
+
+        //The hole left by the middle item keeps its column, the last item is still placed
+        layouter.relayout((400, 100), &|l| if l == w[1] { None } else { Some((30, 12)) }).unwrap();
+        let mut rects = HashMap::new();
+        layouter.update(&mut |item, rect| { rects.insert(item, rect); });
+
+        assert_eq!(rects.len(), 2);
+        assert_eq!(rects[&w[0]], (12, 12, 120, 12));
+        assert_eq!(rects[&w[2]], (268, 12, 120, 12));
+    }
+
+    #[test]
+    fn test_grid_weights_and_limits() {
+        let w: Vec<WidgetHandle> = (0..5).map(WidgetId::from_index).collect();
+        let sizes = |l| Some(if l == w[0] { (40, 18) } else if l == w[2] { (60, 18) } else { (250, 30) });
+        let solve = |access: &Fn(&mut AccessLayout)| {
+            let mut layouter = GridLayout::new().set_size(400, 200);
+            layouter.access(access, &sizes).unwrap();
+
+            let mut rects = HashMap::new();
+            layouter.update(&mut |item, rect| { rects.insert(item, rect); });
+            rects
+        };
+
+        //The label column is as wide as its widest label in every row
+        let rects = solve(&|ref mut l| {
+            l.add(w[0]).add(w[1]).wrap();
+            l.add(w[2]).add(w[3]).wrap();
+            l.add(w[4]).span(2, 1);
+            l.column_weight(0, 0);
+        });
+        assert_eq!(rects[&w[0]], (12, 12, 60, 30));
+        assert_eq!(rects[&w[1]], (80, 12, 308, 30));
+        assert_eq!(rects[&w[2]], (12, 50, 60, 30));
+        assert_eq!(rects[&w[3]], (80, 50, 308, 30));
+        assert_eq!(rects[&w[4]], (12, 88, 376, 30));
+
+        let rects = solve(&|ref mut l| {
+            l.add(w[0]).add(w[1]).wrap();
+            l.add(w[2]).add(w[3]);
+            l.column_weight(0, 1).column_weight(1, 3).row_weight(1, 1);
+        });
+        assert_eq!(rects[&w[0]], (12, 12, 92, 30));
+        assert_eq!(rects[&w[3]], (112, 50, 276, 138));
+
+        //Limits win over the weights, space nobody takes is left at the end
+        let rects = solve(&|ref mut l| {
+            l.add(w[0]).add(w[1]);
+            l.column_width(0, 100).column_limits(1, 0, 200);
+        });
+        assert_eq!(rects[&w[0]], (12, 12, 100, 30));
+        assert_eq!(rects[&w[1]], (120, 12, 200, 30));
+    }
+
+    #[test]
+    fn test_grid_alignment() {
+        let w: Vec<WidgetHandle> = (0..2).map(WidgetId::from_index).collect();
+        let sizes = |l| Some(if l == w[0] { (40, 18) } else { (250, 30) });
+
+        let mut layouter = GridLayout::new().set_size(400, 200);
+        layouter.access(&|ref mut l| {
+            l.add(w[0]).align(Alignment::Center, Alignment::Center);
+            l.add(w[1]).align(Alignment::End, Alignment::Start);
+        }, &sizes).unwrap();
+
+        let mut rects = HashMap::new();
+        layouter.update(&mut |item, rect| { rects.insert(item, rect); });
+
+        //Items keep their standard size, but never grow past their cell
+        assert_eq!(rects[&w[0]], (84, 18, 40, 18));
+        assert_eq!(rects[&w[1]], (204, 12, 184, 30));
+
+        let mut layouter = GridLayout::new();
+        assert_eq!(layouter.access(&|ref mut l| { l.align(Alignment::Start, Alignment::Start); }, &sizes),
+                   Err(LayoutError::NoCellToAlign{column: 0, row: 0}));
+    }
+
+    #[test]
+    fn test_grid_spacing() {
+        let w: Vec<WidgetHandle> = (0..4).map(WidgetId::from_index).collect();
+
+        let mut layouter = GridLayout::new().set_size(400, 200);
+        layouter.access(&|ref mut l| {
+            l.add(w[0]).add(w[1]).add(w[2]).wrap();
+            l.add(w[3]);
+            l.margins(4, 6, 10, 20).gaps(2, 5).padding(3);
+            l.column_gap(0, 20).row_gap(0, 10);
+        }, &|_| Some((30, 12))).unwrap();
+
+        let mut rects = HashMap::new();
+        layouter.update(&mut |item, rect| { rects.insert(item, rect); });
+
+        //The first column is set apart from the others, the rows are as high as their padded items
+        assert_eq!(rects[&w[0]], (7, 9, 115, 12));
+        assert_eq!(rects[&w[1]], (148, 9, 116, 12));
+        assert_eq!(rects[&w[2]], (272, 9, 115, 12));
+        assert_eq!(rects[&w[3]], (7, 37, 115, 12));
+
+        //Oversized values from data files leave no room but don't overflow
+        let mut layouter = GridLayout::new().set_size(400, 200);
+        assert_eq!(layouter.access(&|ref mut l| {
+            l.add(w[0]).add(w[1]);
+            l.padding(::std::u32::MAX / 2 + 1).gaps(::std::u32::MAX, ::std::u32::MAX);
+        }, &|_| Some((30, 12))), Ok(()));
+        layouter.update(&mut |item, rect| { rects.insert(item, rect); });
+        assert_eq!((rects[&w[0]].2, rects[&w[0]].3), (0, 0));
+    }
+
+    #[test]
+    fn test_box_layout() {
+        let w: Vec<WidgetHandle> = (0..3).map(WidgetId::from_index).collect();
+        let sizes = |l| if l == w[2] { Some((60, 20)) } else { Some((100, 40)) };
+        let rects = |layout: &BoxLayout| {
+            let mut rects = Vec::new();
+            layout.update(&mut |item, rect| rects.push((item, rect)));
+            rects
+        };
+
+        let mut column = BoxLayout::new(Direction::Vertical);
+        for &item in &w { column.add(item); }
+        assert!(column.set_stretch(w[1], 1));
+        assert!(column.set_item_alignment(w[2], Some(Alignment::Center)));
+        column.relayout((300, 300), &sizes);
+
+        //The stretched item takes the space left over, the others fill the width
+        assert_eq!(rects(&column), vec![(w[0], (12, 12, 276, 40)),
+                                        (w[1], (12, 60, 276, 200)),
+                                        (w[2], (120, 268, 60, 20))]);
+
+        let mut row = BoxLayout::new(Direction::Horizontal);
+        row.add(w[0]);
+        row.add(w[2]);
+        row.add(w[2]);
+        row.set_spacing(4);
+        row.set_alignment(Alignment::End);
+        assert!(!row.set_stretch(w[1], 1));
+        row.relayout((200, 100), &sizes);
+        assert_eq!(rects(&row), vec![(w[0], (12, 48, 100, 40)), (w[2], (116, 68, 60, 20))]);
+
+        //Rejected items are taken out
+        row.relayout((200, 100), &|l| if l == w[0] { None } else { sizes(l) });
+        assert_eq!(rects(&row), vec![(w[2], (12, 68, 60, 20))]);
+    }
+}
@@ -0,0 +1,13 @@
+#![allow(dead_code)]
+#![allow(unused_variables)]
+#[cfg(feature = "conrod")]
+extern crate conrod;
+#[cfg(feature = "crossterm")]
+extern crate crossterm;
+extern crate cassowary;
+extern crate image;
+extern crate rusttype;
+
+pub mod gui;
+pub mod layout;
+pub mod backend;
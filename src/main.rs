@@ -5,16 +5,17 @@ extern crate conrod;
 extern crate image;
 extern crate cassowary;
 
-use conrod::{widget, text, Colorable, Positionable, Sizeable, Borderable, Labelable, Widget};
+use conrod::{widget, Positionable, Sizeable, Widget};
 use conrod::backend::glium::glium;
 use conrod::backend::glium::glium::{DisplayBuild, Surface};
 
 extern crate cgmath;
+extern crate gliumtest;
 
-mod gui;
-mod layout;
-use gui::UI;
-use std::collections::HashMap;
+use gliumtest::gui;
+use gliumtest::gui::UI;
+use gliumtest::backend::RenderBackend;
+use gliumtest::backend::conrod::{ConrodBackend, ConrodTextMeasurer};
 
 // Load the Rust logo from our assets folder to use as an example image.
 fn load_rust_logo(display: &glium::Display) -> glium::texture::SrgbTexture2d {
@@ -32,6 +33,131 @@ fn load_rust_logo(display: &glium::Display) -> glium::texture::SrgbTexture2d {
     texture
 }
 
+fn translate_key(key: glium::glutin::VirtualKeyCode) -> gui::Key {
+    use glium::glutin::VirtualKeyCode as K;
+
+    match key {
+        K::Back => gui::Key::Backspace,
+        K::Delete => gui::Key::Delete,
+        K::Return => gui::Key::Enter,
+        K::Escape => gui::Key::Escape,
+        K::Tab => gui::Key::Tab,
+        K::Space => gui::Key::Space,
+        K::Left => gui::Key::Left,
+        K::Right => gui::Key::Right,
+        K::Up => gui::Key::Up,
+        K::Down => gui::Key::Down,
+        K::Home => gui::Key::Home,
+        K::End => gui::Key::End,
+        K::PageUp => gui::Key::PageUp,
+        K::PageDown => gui::Key::PageDown,
+        K::A => gui::Key::Character('a'),
+        K::B => gui::Key::Character('b'),
+        K::C => gui::Key::Character('c'),
+        K::D => gui::Key::Character('d'),
+        K::E => gui::Key::Character('e'),
+        K::F => gui::Key::Character('f'),
+        K::G => gui::Key::Character('g'),
+        K::H => gui::Key::Character('h'),
+        K::I => gui::Key::Character('i'),
+        K::J => gui::Key::Character('j'),
+        K::K => gui::Key::Character('k'),
+        K::L => gui::Key::Character('l'),
+        K::M => gui::Key::Character('m'),
+        K::N => gui::Key::Character('n'),
+        K::O => gui::Key::Character('o'),
+        K::P => gui::Key::Character('p'),
+        K::Q => gui::Key::Character('q'),
+        K::R => gui::Key::Character('r'),
+        K::S => gui::Key::Character('s'),
+        K::T => gui::Key::Character('t'),
+        K::U => gui::Key::Character('u'),
+        K::V => gui::Key::Character('v'),
+        K::W => gui::Key::Character('w'),
+        K::X => gui::Key::Character('x'),
+        K::Y => gui::Key::Character('y'),
+        K::Z => gui::Key::Character('z'),
+        K::Key0 => gui::Key::Character('0'),
+        K::Key1 => gui::Key::Character('1'),
+        K::Key2 => gui::Key::Character('2'),
+        K::Key3 => gui::Key::Character('3'),
+        K::Key4 => gui::Key::Character('4'),
+        K::Key5 => gui::Key::Character('5'),
+        K::Key6 => gui::Key::Character('6'),
+        K::Key7 => gui::Key::Character('7'),
+        K::Key8 => gui::Key::Character('8'),
+        K::Key9 => gui::Key::Character('9'),
+        K::F1 => gui::Key::Function(1),
+        K::F2 => gui::Key::Function(2),
+        K::F3 => gui::Key::Function(3),
+        K::F4 => gui::Key::Function(4),
+        K::F5 => gui::Key::Function(5),
+        K::F6 => gui::Key::Function(6),
+        K::F7 => gui::Key::Function(7),
+        K::F8 => gui::Key::Function(8),
+        K::F9 => gui::Key::Function(9),
+        K::F10 => gui::Key::Function(10),
+        K::F11 => gui::Key::Function(11),
+        K::F12 => gui::Key::Function(12),
+        _ => gui::Key::Unknown
+    }
+}
+
+struct Menus {
+    resume: gui::WidgetHandle,
+    quit: gui::WidgetHandle,
+}
+
+fn create_menus(ui: &mut UI) -> Result<Menus, gui::UiError> {
+    let main_form = ui.add_widget(gui::Parent::Root, gui::new_form(50, 50, 400, 300, "Main Menu"))?;
+    let main_label = ui.add_widget(main_form, gui::new_label(10, 40, "Hello."))?;
+    let main_button = ui.add_widget(main_form, gui::new_button(10, 40, 100, 40, "OK."))?;
+    let a_label = ui.add_widget(main_form, gui::new_label(20, 100, "Hello again."))?;
+    let another_form = ui.add_widget(gui::Parent::Root, gui::new_form(300, 300, 300, 300,"Other form"))?;
+    let game_label = ui.add_widget(another_form, gui::new_label(0, 0, "RPG"))?;
+    let a_button1 = ui.add_widget(another_form, gui::new_button(350, 40, 200, 40, "Resume Quest"))?;
+    let a_button2 = ui.add_widget(another_form, gui::new_button(350, 100, 200, 40, "New Character"))?;
+    let a_button3 = ui.add_widget(another_form, gui::new_button(350, 150, 200, 40, "Credits"))?;
+    let a_button4 = ui.add_widget(another_form, gui::new_button(350, 200, 200, 40, "Quit"))?;
+    ui.set_name(main_form, "main_menu");
+    ui.set_name(another_form, "rpg_menu");
+    ui.set_name(a_button1, "resume");
+    ui.set_name(a_button2, "new_character");
+    ui.set_name(a_button3, "credits");
+    ui.set_name(a_button4, "quit");
+    ui.set_layout(main_form, &|ref mut l| {
+        l.add(main_label);
+        l.add(main_button);
+        l.add(a_label);
+    })?;
+    ui.set_box_layout(another_form, gui::Layout::Vertical)?;
+    ui.set_item_alignment(game_label, Some(gliumtest::layout::Alignment::Center));
+
+    ui.on_click(a_button2, |ui, _| {
+        if let Err(e) = open_character_form(ui) {
+            println!("Couldn't open the character form: {}", e);
+        }
+    });
+
+    Ok(Menus { resume: a_button1, quit: a_button4 })
+}
+
+fn open_character_form(ui: &mut UI) -> Result<(), gui::UiError> {
+    //Only one character form is open at a time
+    if let Some(character_form) = ui.find("character_form") {
+        ui.move_widget_to_front(character_form);
+        return Ok(());
+    }
+
+    let character_form = ui.add_widget(gui::Parent::Root, gui::new_form(120, 120, 300, 150, "New Character"))?;
+    ui.set_name(character_form, "character_form");
+    ui.add_widget(character_form, gui::new_label(10, 20, "Name"))?;
+    let name = ui.add_widget(character_form, gui::new_textbox(10, 50, 250, 30, ""))?;
+    ui.set_name(name, "name");
+    ui.set_keyboard_focus(Some(name));
+    ui.on_close(character_form, |ui, _| { ui.remove_widget(character_form); });
+    Ok(())
+}
 
 fn main() {
     const WIDTH: u32 = 800;
@@ -50,45 +176,24 @@ fn main() {
     const FONT_PATH: &'static str =
         concat!(env!("CARGO_MANIFEST_DIR"), "/assets/fonts/Dogma/Dogma.ttf");
     conrod_ui.fonts.insert_from_file(FONT_PATH).unwrap();
-    let font_handle = ui.fonts.insert_from_file(FONT_PATH).unwrap();
-    ui.default_font = Some(font_handle);
+    let font = conrod::text::font::from_file(FONT_PATH).unwrap();
+    ui.set_text_measurer(Box::new(ConrodTextMeasurer::new(font)));
 
     let mut renderer = conrod::backend::glium::Renderer::new(&display).unwrap();
     let image_map = conrod::image::Map::<glium::texture::Texture2d>::new();
 
     
-    let main_form = ui.add_widget(-1, gui::new_form(50, 50, 400, 300, "Main Menu"));
-    let main_label = ui.add_widget(main_form, gui::new_label(10, 40, "Hello."));
-    let main_button = ui.add_widget(main_form, gui::new_button(10, 40, 100, 40, "OK."));
-    let a_label = ui.add_widget(main_form, gui::new_label(20, 100, "Hello again."));
-    let another_form = ui.add_widget(-1, gui::new_form(300, 300, 300, 300,"Other form"));
-    let game_label = ui.add_widget(another_form, gui::new_label(0, 0, "RPG"));
-    let a_button1 = ui.add_widget(another_form, gui::new_button(350, 40, 200, 40, "Resume Quest"));
-    let a_button2 = ui.add_widget(another_form, gui::new_button(350, 100, 200, 40, "New Character"));
-    let a_button3 = ui.add_widget(another_form, gui::new_button(350, 150, 200, 40, "Credits"));
-    let a_button4 = ui.add_widget(another_form, gui::new_button(350, 200, 200, 40, "Quit"));
-    ui.set_layout(main_form, &|ref mut l| {
-        l.add(main_label);
-        l.add(main_button);
-        l.add(a_label);
-    });
-    ui.set_layout(another_form, &|ref mut l| {
-        l.add(game_label).wrap();
-        l.add(a_button1).wrap();
-        l.add(a_button2).wrap();
-        l.add(a_button3).wrap();
-        l.add(a_button4).wrap();
-    });
+    let menu = match create_menus(&mut ui) {
+        Ok(menu) => menu,
+        Err(e) => { println!("Couldn't create the menus: {}", e); return; }
+    };
 
 	let mut running = true;
     let mut mouse_x = 0;
     let mut mouse_y = 0;
+    let mut modifiers = gui::Modifiers::none();
 
-    let mut widgets_collection: 
-        HashMap<gui::WidgetHandle, conrod::widget::id::Id> = HashMap::new();
-    	
-    let mut half_screen_w = WIDTH as f64 / 2.0;
-    let mut half_screen_h = HEIGHT as f64 / 2.0;
+    let mut backend = ConrodBackend::new();
 
 // The `WidgetId` for our background and `Image` widgets.
     widget_ids!(struct Ids { rust_logo });
@@ -100,10 +205,8 @@ fn main() {
     let rust_logo = image_map.insert(rust_logo);
 
 
-    use glium::glutin::{Event, ElementState, MouseButton};
+    use glium::glutin::{Event, ElementState, MouseButton, VirtualKeyCode};
     while running {
-        ui.clear_events();
-
         let mut target = display.draw();
         
         for ev in display.poll_events() {
@@ -125,6 +228,31 @@ fn main() {
                         _ => ()
                     }
                 }
+                Event::KeyboardInput(state, _, Some(key)) => {
+                    let pressed = state == ElementState::Pressed;
+                    match key {
+                        VirtualKeyCode::LShift | VirtualKeyCode::RShift => modifiers.shift = pressed,
+                        VirtualKeyCode::LControl | VirtualKeyCode::RControl => modifiers.ctrl = pressed,
+                        VirtualKeyCode::LAlt | VirtualKeyCode::RAlt => modifiers.alt = pressed,
+                        VirtualKeyCode::LWin | VirtualKeyCode::RWin => modifiers.logo = pressed,
+                        _ => {
+                            if pressed { ui.keydown(translate_key(key), modifiers); }
+                            else { ui.keyup(translate_key(key), modifiers); }
+                        }
+                    }
+                }
+                Event::ReceivedCharacter(c) => {
+                    //Ctrl shortcuts arrive as characters too, those are handled by keydown
+                    if !modifiers.ctrl { ui.text_input(&c.to_string()); }
+                }
+                _ => ()
+            }
+        }
+
+        for event in ui.drain_events() {
+            match event {
+                gui::WidgetEvent::ButtonClicked(handle) if handle == menu.resume => println!("Resume Quest"),
+                gui::WidgetEvent::ButtonClicked(handle) if handle == menu.quit => running = false,
                 _ => ()
             }
         }
@@ -132,94 +260,13 @@ fn main() {
         let render_jobs = ui.render();
 
         target.clear_color(0.0, 0.0, 0.0, 1.0);
-        
-        {
-            {
-                let mut widget_generator = conrod_ui.widget_id_generator();
-                for i in 0 .. ui.num_widgets()  {
-                    let handle = i as gui::WidgetHandle;
-                    let id  = match widgets_collection.get(&handle) {
-                        Some(_) => continue,
-                        None => widget_generator.next()
-                    };
-
-                    widgets_collection.insert(handle, id);    
-                }
-            }
 
+        backend.free_removed_ids(&ui);
+        {
             let ui = &mut conrod_ui.set_widgets();
 
-             widget::Image::new(rust_logo).w_h(w as f64, h as f64).middle().set(ids.rust_logo, ui);
-
-            for render_job in &render_jobs {
-                let i: conrod::widget::id::Id;
-
-                macro_rules! find_widget {
-                    ($collection:ident, $index:ident, $dest:ident) => {
-                        match $collection.get(&$index) {
-                            Some(k) => $dest = *k,
-                            None => unreachable!()
-                        }
-                    }
-                }
-
-                match *render_job {
-                    gui::RenderJob::Nul => (),
-                    gui::RenderJob::Form { index, focus, x, y, w, h, ref title} => {
-                        find_widget!(widgets_collection, index, i);
-
-                        let mut framecolor =  conrod::color::grayscale(0.575);
-                        if focus {framecolor = conrod::color::grayscale(0.650);}
-    
-                        let x = x as f64 - half_screen_w + (w as f64 / 2.0);
-                        let y = half_screen_h - y as f64 - (h as f64/ 2.0);
-
-                        widget::Toggle::new(true)
-                            .x_y(x, y)
-                            .w_h(w as f64, h as f64)
-                            .color(framecolor)
-                            .border(3.0)
-                            .set(i, ui);
-
-                    }
-                    gui::RenderJob::Button {index, pressed, focus, x, y, w, h, ref text } => {
-                        find_widget!(widgets_collection, index, i);
-
-                        let mut label_color = conrod::color::CHARCOAL;;
-                        let mut bgcolor = conrod::color::DARK_BLUE;
-                        if pressed {label_color = conrod::color::LIGHT_CHARCOAL; }
-                        else {
-                            if focus {bgcolor = conrod::color::GRAY;}
-                            else {bgcolor = conrod::color::LIGHT_CHARCOAL;}
-                        }    
-                    
-
-                        widget::Toggle::new(!pressed)
-                            .top_left_of(ui.window)
-                            .x_y(x as f64 - 400.0 + (w as f64 / 2.0), 300.0 - y as f64 - (h as f64/ 2.0))
-                            .w_h(w as f64, h as f64)
-                            .color(bgcolor)
-                            .label(&text)
-                            .label_color(label_color)
-                            .set(i, ui);
-                    },
-                    gui::RenderJob::Label {index, x, y, ref text} => {
-                        find_widget!(widgets_collection, index, i);
-
-                        let fx = x as f64;
-                        let fy = y as f64;
-
-                        widget::Text::new(&text)
-                            .top_left_of(ui.window)
-                            .x_y(fx - 400.0, 300.0 - fy)
-                            .w_h(0.1, 0.1)
-                            .no_line_wrap()
-                            .font_size(18)
-                            .color(conrod::color::WHITE)
-                            .set(i, ui);    
-                    }
-                }
-            }
+            widget::Image::new(rust_logo).w_h(w as f64, h as f64).middle().set(ids.rust_logo, ui);
+            backend.draw(&render_jobs, ui);
         }
         
         renderer.fill(&display, conrod_ui.draw(), &image_map);